@@ -0,0 +1,394 @@
+//! Run-length codec benchmarks, comparing against the previous nom implementation.
+//! Run with `cargo +nightly bench --bench hip_rle`
+#![feature(test)]
+
+extern crate test;
+
+use arcsys::bbcf::hip::{
+    rle::{decode_runs, encode_runs},
+    BBCFHip, BBCFHipFlags, BBCFHipImage, DEFAULT_VERSION,
+};
+use arcsys::{IndexedImage, RGBAColor};
+use test::{black_box, Bencher};
+
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 1024;
+
+/// The indexed HIP parser and rebuilder as they were before the shared run-length codec,
+/// trimmed down to indexed images
+mod previous {
+    use std::io::Write;
+
+    use arcsys::{Error, IndexedImage, Palette, RGBAColor};
+    use byteorder::{WriteBytesExt, LE};
+    use nom::{
+        bytes::complete::{tag, take},
+        error::ErrorKind,
+        number::complete::{le_u32, le_u8},
+        IResult,
+    };
+
+    pub struct BBCFHip {
+        pub version: u32,
+        pub texture_dimensions: (u32, u32),
+        pub unknown: [u8; 3],
+        pub extra_header_data: Option<BBCFHipExtra>,
+        pub width: u32,
+        pub height: u32,
+        pub data: IndexedImage,
+    }
+
+    #[derive(Clone)]
+    pub struct BBCFHipExtra {
+        pub x_offset: u32,
+        pub y_offset: u32,
+        pub extra: Vec<u8>,
+    }
+
+    impl BBCFHipExtra {
+        pub fn size(&self) -> u32 {
+            0x10 + self.extra.len() as u32
+        }
+    }
+
+    impl BBCFHip {
+        pub fn parse(input: &[u8]) -> Result<BBCFHip, Error> {
+            match parse_hip_impl(input) {
+                Ok(([], hip)) => Ok(hip),
+                Ok(_) => Err(Error::Parser("slice not consumed".into())),
+                Err(e) => Err(Error::Parser(e.to_string())),
+            }
+        }
+    }
+
+    fn parse_hip_impl(i: &[u8]) -> IResult<&[u8], BBCFHip> {
+        let (i, _) = tag("HIP\0")(i)?;
+        let (i, version) = le_u32(i)?;
+        let (i, _file_size) = le_u32(i)?;
+        let (i, palette_size) = le_u32(i)?;
+        let (i, texture_w) = le_u32(i)?;
+        let (i, texture_h) = le_u32(i)?;
+        let (i, encoding_tag) = nom::number::complete::u8(i)?;
+        let (i, unk) = take(3usize)(i)?;
+        let (i, extra_header_size) = le_u32(i)?;
+
+        let mut width = texture_w;
+        let mut height = texture_h;
+
+        let (i, extra_header_data) = if extra_header_size >= 0x10 {
+            let (i, w) = le_u32(i)?;
+            let (i, h) = le_u32(i)?;
+
+            width = w;
+            height = h;
+
+            let (i, x_offset) = le_u32(i)?;
+            let (i, y_offset) = le_u32(i)?;
+            let (i, other) = take(extra_header_size - 0x10)(i)?;
+
+            let extra_header = BBCFHipExtra {
+                x_offset,
+                y_offset,
+                extra: other.to_vec(),
+            };
+
+            (i, Some(extra_header))
+        } else {
+            (i, None)
+        };
+
+        if encoding_tag != 0x01 {
+            return Err(nom::Err::Error(nom::error::Error {
+                input: &[],
+                code: ErrorKind::NoneOf,
+            }));
+        }
+
+        let (i, palette) = parse_palette(i, palette_size)?;
+        let (i, image) = parse_index_runs(i, width, height)?;
+
+        let hip = BBCFHip {
+            version,
+            texture_dimensions: (texture_w, texture_h),
+            unknown: [unk[0], unk[1], unk[2]],
+            extra_header_data,
+            width,
+            height,
+            data: IndexedImage { palette, image },
+        };
+
+        Ok((i, hip))
+    }
+
+    fn parse_bgra(i: &[u8]) -> IResult<&[u8], RGBAColor> {
+        let (i, blue) = le_u8(i)?;
+        let (i, green) = le_u8(i)?;
+        let (i, red) = le_u8(i)?;
+        let (i, alpha) = le_u8(i)?;
+
+        Ok((
+            i,
+            RGBAColor {
+                red,
+                green,
+                blue,
+                alpha,
+            },
+        ))
+    }
+
+    fn parse_palette(i: &[u8], palette_length: u32) -> IResult<&[u8], Vec<RGBAColor>> {
+        let mut palette = Vec::new();
+
+        let i = (0..palette_length).try_fold(i, |i, _| {
+            let (i, palette_entry) = parse_bgra(i)?;
+            palette.push(palette_entry);
+            Ok(i)
+        })?;
+
+        Ok((i, palette))
+    }
+
+    pub fn parse_index_runs(mut i: &[u8], width: u32, height: u32) -> IResult<&[u8], Vec<u8>> {
+        let len = width * height;
+        let mut contents = Vec::new();
+
+        while contents.len() != len as usize {
+            let (new_i, mut image_content) = parse_index_run(i)?;
+            contents.append(&mut image_content);
+
+            i = new_i;
+        }
+
+        Ok((i, contents))
+    }
+
+    fn parse_index_run(i: &[u8]) -> IResult<&[u8], Vec<u8>> {
+        let (i, index) = le_u8(i)?;
+        let (i, len) = le_u8(i)?;
+
+        let run = (0..len).map(|_| index).collect::<Vec<u8>>();
+
+        Ok((i, run))
+    }
+
+    const HEADER_SIZE: u32 = 0x20;
+
+    impl BBCFHip {
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut image_bytes = indexed_to_run_encoded(self.data.clone());
+
+            let mut final_bytes = Vec::new();
+
+            final_bytes.write_all(b"HIP\0").unwrap();
+            final_bytes.write_u32::<LE>(self.version).unwrap();
+            final_bytes
+                .write_u32::<LE>(
+                    HEADER_SIZE
+                        + self.extra_header_data.as_ref().map_or(0, |x| x.size())
+                        + image_bytes.len() as u32,
+                )
+                .unwrap();
+            final_bytes
+                .write_u32::<LE>(self.data.palette.len() as u32)
+                .unwrap();
+
+            if let Some(header) = self.extra_header_data.clone() {
+                final_bytes
+                    .write_u32::<LE>(self.texture_dimensions.0)
+                    .unwrap();
+                final_bytes
+                    .write_u32::<LE>(self.texture_dimensions.1)
+                    .unwrap();
+
+                final_bytes.write_u8(0x01).unwrap();
+                final_bytes.write_all(&self.unknown).unwrap();
+
+                final_bytes.write_u32::<LE>(header.size()).unwrap();
+
+                final_bytes.write_u32::<LE>(self.width).unwrap();
+                final_bytes.write_u32::<LE>(self.height).unwrap();
+                final_bytes.write_u32::<LE>(header.x_offset).unwrap();
+                final_bytes.write_u32::<LE>(header.y_offset).unwrap();
+                final_bytes.extend(header.extra);
+            } else {
+                final_bytes.write_u32::<LE>(self.width).unwrap();
+                final_bytes.write_u32::<LE>(self.height).unwrap();
+
+                final_bytes.write_u8(0x01).unwrap();
+                final_bytes.write_all(&self.unknown).unwrap();
+
+                final_bytes.write_u32::<LE>(0).unwrap();
+            }
+
+            final_bytes.write_u32::<LE>(self.width).unwrap();
+            final_bytes.write_u32::<LE>(self.height).unwrap();
+
+            final_bytes.append(&mut image_bytes);
+
+            final_bytes
+        }
+    }
+
+    pub fn indexed_to_run_encoded(indexed: IndexedImage) -> Vec<u8> {
+        let mut palette = indexed.get_palette_bytes();
+
+        let mut final_image = Vec::new();
+
+        final_image.append(&mut palette);
+
+        let mut run_length = 0;
+        let mut indexes = indexed.image.into_iter().peekable();
+        while let Some(i) = indexes.next() {
+            run_length += 1;
+
+            if run_length == u8::MAX {
+                final_image.push(i);
+                final_image.push(run_length);
+
+                run_length = 0;
+                continue;
+            }
+
+            if let Some(next) = indexes.peek() {
+                if i == *next {
+                    continue;
+                } else {
+                    final_image.push(i);
+                    final_image.push(run_length);
+
+                    run_length = 0;
+                    continue;
+                }
+            } else {
+                final_image.push(i);
+                final_image.push(run_length);
+            }
+        }
+
+        final_image
+    }
+}
+
+/// A sprite-like image, mostly long transparent runs with short noisy spans
+fn sprite() -> Vec<u8> {
+    (0..WIDTH * HEIGHT)
+        .map(|i| {
+            let (x, y) = (i % WIDTH, i / WIDTH);
+            if (256..768).contains(&x) && (128..896).contains(&y) {
+                ((x / 3) ^ (y / 5)) as u8
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+fn sprite_palette() -> Vec<RGBAColor> {
+    (0..=255)
+        .map(|i| RGBAColor {
+            red: i,
+            green: i,
+            blue: i,
+            alpha: 0xFF,
+        })
+        .collect()
+}
+
+#[bench]
+fn decode(b: &mut Bencher) {
+    let mut encoded = Vec::new();
+    encode_runs(&sprite(), &mut encoded);
+
+    b.iter(|| decode_runs::<u8>(black_box(&encoded), WIDTH * HEIGHT).unwrap());
+}
+
+#[bench]
+fn decode_previous(b: &mut Bencher) {
+    let mut encoded = Vec::new();
+    encode_runs(&sprite(), &mut encoded);
+
+    b.iter(|| {
+        previous::parse_index_runs(black_box(&encoded), WIDTH as u32, HEIGHT as u32).unwrap()
+    });
+}
+
+#[bench]
+fn encode(b: &mut Bencher) {
+    let image = sprite();
+
+    b.iter(|| {
+        let mut encoded = Vec::new();
+        encode_runs(black_box(&image), &mut encoded);
+        encoded
+    });
+}
+
+#[bench]
+fn encode_previous(b: &mut Bencher) {
+    let image = IndexedImage {
+        palette: Vec::new(),
+        image: sprite(),
+    };
+
+    // the previous encoders took the image by value, so the clone is part of the cost
+    b.iter(|| previous::indexed_to_run_encoded(black_box(&image).clone()));
+}
+
+fn sprite_hip() -> BBCFHip {
+    BBCFHip {
+        version: DEFAULT_VERSION,
+        texture_dimensions: (WIDTH as u32, HEIGHT as u32),
+        flags: BBCFHipFlags::default(),
+        extra_header_data: None,
+        image: BBCFHipImage::Indexed {
+            width: WIDTH as u32,
+            height: HEIGHT as u32,
+            data: IndexedImage {
+                palette: sprite_palette(),
+                image: sprite(),
+            },
+        },
+        quirks: Default::default(),
+    }
+}
+
+#[bench]
+fn parse_hip(b: &mut Bencher) {
+    let bytes = sprite_hip().to_bytes().unwrap();
+
+    b.iter(|| BBCFHip::parse(black_box(&bytes)).unwrap());
+}
+
+#[bench]
+fn parse_hip_previous(b: &mut Bencher) {
+    // both parsers read the same layout
+    let bytes = sprite_hip().to_bytes().unwrap();
+
+    b.iter(|| previous::BBCFHip::parse(black_box(&bytes)).unwrap());
+}
+
+#[bench]
+fn rebuild_hip(b: &mut Bencher) {
+    let hip = sprite_hip();
+
+    b.iter(|| black_box(&hip).to_bytes().unwrap());
+}
+
+#[bench]
+fn rebuild_hip_previous(b: &mut Bencher) {
+    let hip = previous::BBCFHip {
+        version: DEFAULT_VERSION,
+        texture_dimensions: (WIDTH as u32, HEIGHT as u32),
+        unknown: [0; 3],
+        extra_header_data: None,
+        width: WIDTH as u32,
+        height: HEIGHT as u32,
+        data: IndexedImage {
+            palette: sprite_palette(),
+            image: sprite(),
+        },
+    };
+
+    b.iter(|| black_box(&hip).to_bytes());
+}
@@ -3,7 +3,6 @@ use std::{fs, io::Write, path::{Path, PathBuf}};
 use anyhow::Result as AResult;
 use arcsys::{arcsys_filename_hash, ggacpr::replay::AcprReplay};
 use clap::{Args, Parser, Subcommand};
-use smallvec::SmallVec;
 
 fn main() -> AResult<()> {
     let res = run();
@@ -20,6 +19,9 @@ fn main() -> AResult<()> {
 struct Cmd {
     #[clap(subcommand)]
     subcmd: Type,
+    /// Known filenames, one per line. Names from opened PACs and recovered names are added to it
+    #[clap(long, global = true, default_value = "arcsys_names.txt")]
+    dictionary: PathBuf,
 }
 
 #[derive(Subcommand, Debug)]
@@ -50,6 +52,84 @@ enum Type {
 enum Utility {
     /// Hash bytes (e.g. `0xABCDABCD`) using the arcsys filename hash function
     Hash { bytes: String },
+    /// Search for filenames that produce an arcsys filename hash
+    Unhash {
+        /// The hash to invert (e.g. `0xABCDABCD`)
+        hash: String,
+        /// Maximum length of the unknown part of the name
+        #[clap(short, long, default_value_t = 6)]
+        max_len: usize,
+        /// Known start of the name
+        #[clap(short, long, default_value = "")]
+        prefix: String,
+        /// Known end of the name (e.g. `.hip`)
+        #[clap(short, long, default_value = "")]
+        suffix: String,
+        /// Characters the unknown part may contain
+        #[clap(short, long, default_value = "abcdefghijklmnopqrstuvwxyz0123456789_")]
+        alphabet: String,
+        /// Stop after this many names
+        #[clap(short, long)]
+        limit: Option<usize>,
+    },
+    /// Recover the names of hash-only PAC entries using filename templates
+    Recover {
+        /// The PAC whose entries should be named
+        file_in: PathBuf,
+        /// Filename template (e.g. `{$char}{00-99}_{000-999}.hip`), can be given multiple times
+        #[clap(short, long, required = true)]
+        template: Vec<String>,
+        /// Word list used by templates (e.g. `char=es,ha,ny`), can be given multiple times
+        #[clap(short, long)]
+        list: Vec<String>,
+        /// Write the recovered names to this file, one per line
+        file_out: Option<PathBuf>,
+        /// Allow overwriting files that already exist
+        #[clap(short, long)]
+        overwrite: bool,
+    },
+    /// List byte-identical entries in a PAC archive
+    Duplicates {
+        /// The PAC to inspect
+        file_in: PathBuf,
+        /// Write a rebuilt PAC where identical entries share one data block
+        file_out: Option<PathBuf>,
+        /// Allow overwriting files that already exist
+        #[clap(short, long)]
+        overwrite: bool,
+    },
+    /// Pack every HIP sprite in a PAC archive into PNG sheets with JSON metadata
+    Atlas {
+        /// The PAC holding the sprites
+        file_in: PathBuf,
+        /// Folder to write `sheet_N.png` and `atlas.json` to
+        dir_out: PathBuf,
+        /// Maximum width and height of a sheet
+        #[clap(short, long, default_value_t = 2048)]
+        max_size: u32,
+        /// Allow overwriting files that already exist
+        #[clap(short, long)]
+        overwrite: bool,
+    },
+    /// Render every HIP sprite in a PAC archive into a labeled grid of thumbnails
+    ContactSheet {
+        /// The PAC holding the sprites
+        file_in: PathBuf,
+        /// The PNG to write
+        file_out: PathBuf,
+        /// Width and height of a thumbnail
+        #[clap(short, long, default_value_t = 128)]
+        cell_size: u32,
+        /// Thumbnails per row
+        #[clap(long, default_value_t = 8)]
+        columns: u32,
+        /// HPL to render indexed sprites with instead of their own palette
+        #[clap(short, long)]
+        palette: Option<PathBuf>,
+        /// Allow overwriting files that already exist
+        #[clap(short, long)]
+        overwrite: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -110,20 +190,20 @@ struct FileActionArgs {
 }
 
 fn run() -> AResult<()> {
-    let args = Cmd::parse();
-    match args.subcmd {
+    let cmd = Cmd::parse();
+    match cmd.subcmd {
         Type::Archive { format } => match format {
             PacType::Pac { action } => match action {
-                FileAction::Parse { args } => parse_pac(args),
+                FileAction::Parse { args } => parse_pac(args, &cmd.dictionary),
                 FileAction::Rebuild { args } => rebuild_pac(args),
             },
             PacType::Zcmp { action } => match action {
                 FileAction::Parse { args } => parse_zcmp(args),
-                FileAction::Rebuild { args: _ } => todo!(),
+                FileAction::Rebuild { args } => rebuild_zcmp(args),
             },
             PacType::Dfaspac { action } => match action {
                 FileAction::Parse { args } => parse_dfasfpac(args),
-                FileAction::Rebuild { args: _ } => todo!(),
+                FileAction::Rebuild { args } => rebuild_dfasfpac(args),
             },
         },
         Type::Bin { format } => match format {
@@ -155,6 +235,58 @@ fn run() -> AResult<()> {
 
                 Ok(())
             }
+            Utility::Unhash {
+                hash,
+                max_len,
+                prefix,
+                suffix,
+                alphabet,
+                limit,
+            } => {
+                let hash = u32::from_str_radix(hash.trim_start_matches("0x"), 16)?;
+
+                let names: Vec<String> = arcsys::hash::invert(hash, &alphabet, max_len, &prefix, &suffix)?
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect();
+                for name in names.iter() {
+                    println!("{name}");
+                }
+
+                update_dictionary(&cmd.dictionary, |dictionary| {
+                    names
+                        .iter()
+                        .filter(|name| !name.is_empty() && dictionary.insert(name.as_str()))
+                        .count()
+                })?;
+
+                Ok(())
+            }
+            Utility::Recover {
+                file_in,
+                template,
+                list,
+                file_out,
+                overwrite,
+            } => recover_names(file_in, template, list, file_out, overwrite, &cmd.dictionary),
+            Utility::Duplicates {
+                file_in,
+                file_out,
+                overwrite,
+            } => find_duplicates(file_in, file_out, overwrite),
+            Utility::Atlas {
+                file_in,
+                dir_out,
+                max_size,
+                overwrite,
+            } => build_atlas(file_in, dir_out, max_size, overwrite),
+            Utility::ContactSheet {
+                file_in,
+                file_out,
+                cell_size,
+                columns,
+                palette,
+                overwrite,
+            } => build_contact_sheet(file_in, file_out, cell_size, columns, palette, overwrite),
         },
     }
 }
@@ -164,15 +296,22 @@ use std::io::BufReader;
 use arcsys::ggacpr::obj::{GGXXAudioArray, GGXXCellArray, GGXXCellEntry, GGXXObjBin, GGXXObjEntry, GGXXPaletteArray, GGXXPaletteEntry, GGXXPlayerEntry, GGXXSpriteArray};
 use arcsys::ggacpr::script::{GGXXObjScriptData, GGXXPlayerScriptData};
 
-fn parse_pac(args: FileActionArgs) -> AResult<()> {
-    let pac = arcsys::pac::Pac::open(args.file_in)?;
+fn parse_pac(args: FileActionArgs, dictionary: &Path) -> AResult<()> {
+    let mut pac = arcsys::pac::Pac::open(args.file_in)?;
 
     println!("{:X}: {:?}", pac.pac_style.bits(), pac.pac_style);
 
+    let dictionary = update_dictionary(dictionary, |dictionary| dictionary.harvest(&pac))?;
+    dictionary.resolve(&mut pac);
+
     if let Some(out_path) = args.file_out {
         for entry in pac.entries.iter()
         {
-            write_file(out_path.join(entry.name().unwrap()), args.overwrite, &entry.contents)?;
+            // entries with no known name are written under their hash
+            let name = entry
+                .name()
+                .map_or_else(|| format!("0x{:0>8X}", entry.hash_id()), str::to_string);
+            write_file(out_path.join(name), args.overwrite, &entry.contents)?;
         }
     }
 
@@ -207,6 +346,163 @@ fn rebuild_pac(args: FileActionArgs) -> AResult<()> {
     Ok(())
 }
 
+fn recover_names(
+    file_in: PathBuf,
+    templates: Vec<String>,
+    lists: Vec<String>,
+    file_out: Option<PathBuf>,
+    overwrite: bool,
+    dictionary: &Path,
+) -> AResult<()> {
+    use std::collections::{HashMap, HashSet};
+
+    use arcsys::hash::{NameDictionary, Template};
+
+    let mut pac = arcsys::pac::Pac::open(file_in)?;
+
+    let lists = lists
+        .iter()
+        .map(|list| {
+            let (name, words) = list
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid list `{list}`, expected `name=a,b,c`"))?;
+
+            Ok((name.to_string(), words.split(',').map(str::to_string).collect()))
+        })
+        .collect::<AResult<HashMap<String, Vec<String>>>>()?;
+
+    let targets: HashSet<u32> = pac
+        .entries
+        .iter()
+        .filter(|entry| entry.name().is_none())
+        .map(|entry| entry.hash_id())
+        .collect();
+
+    let mut recovered = NameDictionary::new();
+    for template in templates {
+        let template = Template::parse_with_lists(&template, &lists)?;
+        println!("checking {} candidates", template.count());
+
+        recovered.extend(template.recover(&targets).names().into_iter().map(str::to_string));
+    }
+
+    // names learned earlier can fill in whatever the templates missed
+    let dictionary = update_dictionary(dictionary, |dictionary| {
+        let harvested = dictionary.harvest(&pac);
+        harvested + recovered.names().into_iter().filter(|name| dictionary.insert(*name)).count()
+    })?;
+
+    let report = dictionary.resolve(&mut pac);
+    for index in report.resolved.iter() {
+        let entry = &pac.entries[*index];
+        println!("0x{:0>8X}: {}", entry.hash_id(), entry.name().unwrap());
+    }
+    for collision in report.collisions.iter() {
+        println!("0x{:0>8X}: ambiguous {:?}", collision.hash, collision.candidates);
+    }
+
+    println!(
+        "named {} of {} entries",
+        report.resolved.len(),
+        report.resolved.len() + report.collisions.len() + report.unresolved.len()
+    );
+
+    if let Some(out_path) = file_out {
+        write_file(out_path, overwrite, recovered.to_lines())?;
+    }
+
+    Ok(())
+}
+
+/// Load the dictionary at `path` if there is one, and save it again if `update` added any names
+fn update_dictionary(
+    path: &Path,
+    update: impl FnOnce(&mut arcsys::hash::NameDictionary) -> usize,
+) -> AResult<arcsys::hash::NameDictionary> {
+    let mut dictionary = if path.exists() {
+        arcsys::hash::NameDictionary::open(path)?
+    } else {
+        arcsys::hash::NameDictionary::new()
+    };
+
+    let added = update(&mut dictionary);
+    if added > 0 {
+        fs::write(path, dictionary.to_lines())?;
+        println!("added {added} names to {}", path.display());
+    }
+
+    Ok(dictionary)
+}
+
+fn find_duplicates(file_in: PathBuf, file_out: Option<PathBuf>, overwrite: bool) -> AResult<()> {
+    let pac = arcsys::pac::Pac::open(file_in)?;
+    let report = pac.find_duplicates();
+
+    for group in report.groups.iter() {
+        println!("0x{:016X} ({} bytes):", group.hash, group.size);
+        for index in group.entries.iter() {
+            println!("    {:?}", pac.entries[*index]);
+        }
+    }
+
+    println!(
+        "{} duplicate groups, {} redundant bytes",
+        report.groups.len(),
+        report.redundant_bytes()
+    );
+
+    if let Some(out_path) = file_out {
+        let (bytes, saved) = pac.to_bytes_deduplicated()?;
+        println!("saved {saved} bytes by sharing data offsets");
+
+        write_file(out_path, overwrite, bytes)?;
+    }
+
+    Ok(())
+}
+
+fn build_atlas(file_in: PathBuf, dir_out: PathBuf, max_size: u32, overwrite: bool) -> AResult<()> {
+    let pac = arcsys::pac::Pac::open(file_in)?;
+
+    let mut builder = arcsys::bbcf::hip::AtlasBuilder::new().max_size(max_size);
+    let count = builder.add_pac(&pac)?;
+
+    let atlas = builder.build()?;
+    for (index, png) in atlas.sheets_to_png()?.into_iter().enumerate() {
+        write_file(dir_out.join(format!("sheet_{index}.png")), overwrite, png)?;
+    }
+    write_file(dir_out.join("atlas.json"), overwrite, atlas.metadata_json()?)?;
+
+    println!("packed {count} sprites into {} sheets", atlas.sheets.len());
+
+    Ok(())
+}
+
+fn build_contact_sheet(
+    file_in: PathBuf,
+    file_out: PathBuf,
+    cell_size: u32,
+    columns: u32,
+    palette: Option<PathBuf>,
+    overwrite: bool,
+) -> AResult<()> {
+    let pac = arcsys::pac::Pac::open(file_in)?;
+
+    let mut builder = arcsys::bbcf::hip::ContactSheetBuilder::new()
+        .cell_size(cell_size)
+        .columns(columns);
+    if let Some(palette) = palette {
+        let hpl = arcsys::bbcf::hpl::BBCFHpl::parse(&fs::read(palette)?)?;
+        builder = builder.palette(&hpl);
+    }
+
+    let sheet = builder.build(&pac)?;
+    let png = arcsys::bbcf::hip::BBCFHip::from_full_canvas(sheet, false).to_png(false)?;
+    write_file(file_out, overwrite, png)?;
+
+    Ok(())
+}
+
 fn parse_zcmp(args: FileActionArgs) -> AResult<()> {
     let pac = arcsys::pac::Zcmp::open(&args.file_in)?;
 
@@ -217,6 +513,16 @@ fn parse_zcmp(args: FileActionArgs) -> AResult<()> {
     Ok(())
 }
 
+fn rebuild_zcmp(args: FileActionArgs) -> AResult<()> {
+    let zcmp = arcsys::pac::Zcmp::new(fs::read(args.file_in)?);
+
+    if let Some(out_path) = args.file_out {
+        write_file(out_path, args.overwrite, zcmp.to_bytes())?;
+    }
+
+    Ok(())
+}
+
 fn parse_dfasfpac(args: FileActionArgs) -> AResult<()> {
     let pac = arcsys::pac::DfasFPac::open(args.file_in)?;
 
@@ -227,6 +533,16 @@ fn parse_dfasfpac(args: FileActionArgs) -> AResult<()> {
     Ok(())
 }
 
+fn rebuild_dfasfpac(args: FileActionArgs) -> AResult<()> {
+    let dfasfpac = arcsys::pac::DfasFPac::new(fs::read(args.file_in)?);
+
+    if let Some(out_path) = args.file_out {
+        write_file(out_path, args.overwrite, dfasfpac.to_bytes())?;
+    }
+
+    Ok(())
+}
+
 fn parse_obj(args: FileActionArgs) -> AResult<()> {
     let obj = GGXXObjBin::open(args.file_in)?;
 
@@ -491,60 +807,3 @@ fn write_file(out_path: impl AsRef<Path>, overwrite: bool, data: impl AsRef<[u8]
 
     Ok(())
 }
-
-#[allow(dead_code)]
-fn bruteforce_name(hash: u32) -> String {
-    use rayon::prelude::*;
-
-    let generator = SequenceGenerator {
-        alphabet: &[
-            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
-            'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '1', '2', '3', '4', '5', '6', '7', '8',
-            '9', '0', '_', '.', '/',
-        ],
-        raw_string: SmallVec::new(),
-    };
-
-    let name = generator
-        .par_bridge()
-        .find_first(|s| hash == arcsys_filename_hash(s))
-        .unwrap();
-
-    name
-}
-
-struct SequenceGenerator {
-    alphabet: &'static [char],
-    raw_string: SmallVec<[usize; 64]>,
-}
-
-impl Iterator for SequenceGenerator {
-    type Item = String;
-
-    #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.raw_string.len() > 64 {
-            return None;
-        }
-
-        let mut add_digit = true;
-        for i in &mut self.raw_string {
-            if *i == self.alphabet.len() - 1 {
-                *i = 0;
-            } else {
-                *i += 1;
-                add_digit = false;
-                break;
-            }
-        }
-
-        if add_digit {
-            self.raw_string.push(0);
-            println!("bruteforce string length: {}", self.raw_string.len());
-        }
-
-        let string = String::from_iter(self.raw_string.iter().map(|i| self.alphabet[*i]));
-
-        Some(string)
-    }
-}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{pac::Pac, Error};
+
+use super::{
+    sheet::{pac_sprites, DrawMode, Sheet},
+    BBCFHip, BBCFHipExtra, BBCFHipImage, TRANSPARENT,
+};
+
+/// Packs a set of [`BBCFHip`] sprites into RGBA texture sheets
+pub struct AtlasBuilder {
+    max_size: u32,
+    padding: u32,
+    sprites: Vec<(String, BBCFHip)>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self {
+            max_size: 2048,
+            padding: 1,
+            sprites: Vec::new(),
+        }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum width and height of a sheet, 2048 by default
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Transparent pixels left between sprites, 1 by default
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, hip: BBCFHip) -> &mut Self {
+        self.sprites.push((name.into(), hip));
+        self
+    }
+
+    /// Add every HIP in a PAC, named after their entry or their hash if the entry has no name.
+    /// Returns how many sprites were added
+    pub fn add_pac(&mut self, pac: &Pac) -> Result<usize, Error> {
+        let mut added = 0;
+
+        for sprite in pac_sprites(pac) {
+            let (name, hip) = sprite?;
+            self.add(name, hip);
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Pack the sprites with a shelf packer, tallest first
+    pub fn build(&self) -> Result<Atlas, Error> {
+        let mut order = (0..self.sprites.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.sprites[i].1.image.height()));
+
+        let mut packer = ShelfPacker::new(self.max_size, self.padding);
+        let mut placements = vec![(0, 0, 0); self.sprites.len()];
+
+        for i in order {
+            let (name, hip) = &self.sprites[i];
+            let (width, height) = (hip.image.width(), hip.image.height());
+
+            placements[i] = packer.place(width, height).ok_or_else(|| {
+                Error::Rebuilder(format!(
+                    "sprite {name} ({width}x{height}) doesn't fit in a {0}x{0} sheet",
+                    self.max_size
+                ))
+            })?;
+        }
+
+        let mut sheets = packer
+            .sheets
+            .iter()
+            .map(|&(width, height)| Sheet::new(width, height, TRANSPARENT))
+            .collect::<Vec<_>>();
+
+        let mut sprites = Vec::with_capacity(self.sprites.len());
+        for ((name, hip), &(sheet, x, y)) in self.sprites.iter().zip(&placements) {
+            sheets[sheet].draw(
+                &hip.image.rgba_pixels(),
+                hip.image.width(),
+                x,
+                y,
+                DrawMode::Copy,
+            );
+
+            sprites.push(AtlasSprite {
+                name: name.clone(),
+                sheet,
+                x,
+                y,
+                width: hip.image.width(),
+                height: hip.image.height(),
+                texture_dimensions: hip.texture_dimensions,
+                extra_header_data: hip.extra_header_data.clone(),
+            });
+        }
+
+        Ok(Atlas {
+            sheets: sheets.into_iter().map(Sheet::into_image).collect(),
+            metadata: AtlasMetadata { sprites },
+        })
+    }
+}
+
+/// Packed sheets along with where every sprite ended up
+pub struct Atlas {
+    /// RGBA sheets, referenced by index from [`AtlasSprite::sheet`]
+    pub sheets: Vec<BBCFHipImage>,
+    pub metadata: AtlasMetadata,
+}
+
+impl Atlas {
+    /// Encode every sheet as a PNG
+    pub fn sheets_to_png(&self) -> Result<Vec<Vec<u8>>, Error> {
+        self.sheets
+            .iter()
+            .map(|sheet| BBCFHip::from_full_canvas(sheet.clone(), false).to_png(false))
+            .collect()
+    }
+
+    pub fn metadata_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(&self.metadata).map_err(|e| Error::Rebuilder(e.to_string()))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AtlasMetadata {
+    pub sprites: Vec<AtlasSprite>,
+}
+
+/// A sprite's location in the atlas, along with what's needed to place it back in its frame
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AtlasSprite {
+    pub name: String,
+    pub sheet: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The full frame size of the original HIP
+    pub texture_dimensions: (u32, u32),
+    /// The original extra header, holding the sprite's offsets inside the frame
+    pub extra_header_data: Option<BBCFHipExtra>,
+}
+
+struct ShelfPacker {
+    max_size: u32,
+    padding: u32,
+    /// Used width and height of each sheet
+    sheets: Vec<(u32, u32)>,
+    /// Position and height of the current shelf on the last sheet
+    shelf: (u32, u32, u32),
+}
+
+impl ShelfPacker {
+    fn new(max_size: u32, padding: u32) -> Self {
+        Self {
+            max_size,
+            padding,
+            sheets: Vec::new(),
+            shelf: (0, 0, 0),
+        }
+    }
+
+    /// Find room for a sprite, returning its sheet and position
+    fn place(&mut self, width: u32, height: u32) -> Option<(usize, u32, u32)> {
+        if width > self.max_size || height > self.max_size {
+            return None;
+        }
+
+        let (mut x, mut y, mut shelf_height) = self.shelf;
+
+        if self.sheets.is_empty() {
+            self.sheets.push((0, 0));
+        } else if x + width > self.max_size {
+            // next shelf
+            (x, y, shelf_height) = (0, y + shelf_height + self.padding, 0);
+        }
+
+        if y + height > self.max_size {
+            self.sheets.push((0, 0));
+            (x, y, shelf_height) = (0, 0, 0);
+        }
+
+        let sheet = self.sheets.len() - 1;
+        let used = &mut self.sheets[sheet];
+        used.0 = used.0.max(x + width);
+        used.1 = used.1.max(y + height);
+
+        self.shelf = (x + width + self.padding, y, shelf_height.max(height));
+
+        Some((sheet, x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        helpers::{IndexedImage, RGBAColor},
+        pac::{Compression, PacEntry, PacStyle},
+    };
+
+    fn sprite(width: u32, height: u32, index: u8) -> BBCFHip {
+        let mut hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Indexed {
+                width,
+                height,
+                data: IndexedImage {
+                    palette: vec![
+                        TRANSPARENT,
+                        RGBAColor {
+                            red: index,
+                            green: 0,
+                            blue: 0,
+                            alpha: 255,
+                        },
+                    ],
+                    image: vec![1; (width * height) as usize],
+                },
+            },
+            false,
+        );
+        hip.extra_header_data = Some(BBCFHipExtra::new(index as u32, 0));
+        hip
+    }
+
+    #[test]
+    fn test_atlas() {
+        let mut builder = AtlasBuilder::new().max_size(8).padding(1);
+        builder
+            .add("a", sprite(4, 4, 1))
+            .add("b", sprite(3, 2, 2))
+            .add("c", sprite(8, 8, 3));
+
+        let atlas = builder.build().unwrap();
+        assert_eq!(atlas.sheets.len(), 2);
+
+        let sprites = &atlas.metadata.sprites;
+        assert_eq!((sprites[2].sheet, sprites[2].x, sprites[2].y), (0, 0, 0));
+        assert_eq!((sprites[0].sheet, sprites[0].x, sprites[0].y), (1, 0, 0));
+        assert_eq!((sprites[1].sheet, sprites[1].x, sprites[1].y), (1, 5, 0));
+        assert_eq!(sprites[1].extra_header_data.as_ref().unwrap().x_offset, 2);
+
+        let BBCFHipImage::Raw { width, data, .. } = &atlas.sheets[1] else {
+            panic!("expected a raw sheet");
+        };
+        assert_eq!(*width, 8);
+        assert_eq!(data[5].red, 2);
+        assert_eq!(data[4].alpha, 0);
+
+        assert!(atlas.metadata_json().unwrap().contains("\"name\": \"b\""));
+
+        let pac = Pac {
+            compression: Compression::None,
+            pac_style: PacStyle::ID_ONLY,
+            entries: vec![
+                PacEntry::new_unnamed(0xABCD, sprite(1, 1, 1).to_bytes().unwrap()),
+                PacEntry::new_unnamed(0x1234, vec![0; 4]),
+            ],
+        };
+        let mut from_pac = AtlasBuilder::new();
+        assert_eq!(from_pac.add_pac(&pac).unwrap(), 1);
+        assert_eq!(
+            from_pac.build().unwrap().metadata.sprites[0].name,
+            "0x0000ABCD"
+        );
+
+        let mut too_big = AtlasBuilder::new().max_size(4);
+        too_big.add("c", sprite(8, 8, 3));
+        assert!(too_big.build().is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use super::{
+    edit::{Crop, Placement},
+    render::color_lookup,
+    BBCFHip, BBCFHipExtra, BBCFHipFlags, BBCFHipImage, LumaPixel, DEFAULT_VERSION,
+};
+
+impl BBCFHip {
+    /// Render the (possibly cropped) image onto a canvas the size of `texture_dimensions`,
+    /// using the offsets from the extra header.
+    /// Uncovered pixels are transparent, index 0 or zero luma depending on the image type
+    pub fn to_full_canvas(&self) -> BBCFHipImage {
+        let Some(extra) = &self.extra_header_data else {
+            return self.image.clone();
+        };
+
+        let (canvas_width, canvas_height) = self.texture_dimensions;
+        let placement = Placement {
+            width: self.image.width(),
+            height: self.image.height(),
+            canvas_width,
+            canvas_height,
+            x: extra.x_offset,
+            y: extra.y_offset,
+        };
+
+        self.image
+            .map_pixels(canvas_width, canvas_height, &placement)
+    }
+
+    /// Build a HIP from a full size frame.
+    ///
+    /// With `trim` the image is cropped to the tight bounding box of its visible pixels
+    /// and the offsets are stored in the extra header, otherwise it's kept as-is
+    pub fn from_full_canvas(image: BBCFHipImage, trim: bool) -> BBCFHip {
+        let mut hip = BBCFHip {
+            version: DEFAULT_VERSION,
+            texture_dimensions: (image.width(), image.height()),
+            flags: BBCFHipFlags::default(),
+            extra_header_data: None,
+            image,
+            quirks: Default::default(),
+        };
+
+        if trim {
+            hip.trim();
+        }
+
+        hip
+    }
+
+    /// Replace the image with a full size frame, keeping the rest of the header.
+    /// The frame is re-cropped if this HIP already uses an extra header
+    pub fn set_full_canvas(&mut self, image: BBCFHipImage) {
+        self.texture_dimensions = (image.width(), image.height());
+        self.image = image;
+
+        if let Some(extra) = &mut self.extra_header_data {
+            extra.x_offset = 0;
+            extra.y_offset = 0;
+            self.trim();
+        }
+    }
+
+    /// Crop the image to its visible pixels, moving the offsets into the extra header
+    fn trim(&mut self) {
+        // fully transparent frames keep a single pixel at the origin, empty ones stay empty
+        let has_pixels = self.image.width() > 0 && self.image.height() > 0;
+        let (x, y, w, h) = match self.image.content_bounds() {
+            Some(bounds) => bounds,
+            None if has_pixels => (0, 0, 1, 1),
+            None => (0, 0, 0, 0),
+        };
+
+        let extra = self
+            .extra_header_data
+            .get_or_insert_with(|| BBCFHipExtra::new(0, 0));
+        extra.x_offset += x;
+        extra.y_offset += y;
+
+        let crop = Crop {
+            width: self.image.width(),
+            x,
+            y,
+            w,
+            h,
+        };
+
+        self.image = self.image.map_pixels(w, h, &crop);
+    }
+}
+
+impl BBCFHipImage {
+    /// The tight bounding box `(x, y, width, height)` of all visible pixels,
+    /// or `None` if the image is fully transparent.
+    ///
+    /// Indexed pixels are visible if the color they're drawn with has any alpha,
+    /// see [`BBCFHipImage::to_raw`], luma pixels if their alpha is non-zero
+    pub fn content_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let visible: Vec<bool> = match self {
+            BBCFHipImage::Indexed { data, .. } => {
+                let lookup = color_lookup(&data.palette);
+                data.image
+                    .iter()
+                    .map(|&i| lookup[i as usize].alpha != 0)
+                    .collect()
+            }
+            BBCFHipImage::Raw { data, .. } => data.iter().map(|c| c.alpha != 0).collect(),
+            BBCFHipImage::Luma { data, .. } => data
+                .iter()
+                .map(|&l| LumaPixel::from(l).alpha != 0)
+                .collect(),
+        };
+
+        let width = self.width() as usize;
+        if width == 0 {
+            return None;
+        }
+
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for (i, _) in visible.iter().enumerate().filter(|(_, v)| **v) {
+            let (x, y) = (i % width, i / width);
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+
+        bounds.map(|(x0, y0, x1, y1)| {
+            (
+                x0 as u32,
+                y0 as u32,
+                (x1 - x0 + 1) as u32,
+                (y1 - y0 + 1) as u32,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bbcf::hip::TRANSPARENT,
+        helpers::{IndexedImage, RGBAColor},
+    };
+
+    #[test]
+    fn test_canvas_trim() {
+        // 4x3 canvas with a 2x2 block of index 1 at (1, 1)
+        let palette = vec![
+            TRANSPARENT,
+            RGBAColor {
+                red: 255,
+                green: 255,
+                blue: 255,
+                alpha: 255,
+            },
+        ];
+        let full = BBCFHipImage::Indexed {
+            width: 4,
+            height: 3,
+            data: IndexedImage {
+                palette,
+                image: vec![0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0],
+            },
+        };
+
+        let hip = BBCFHip::from_full_canvas(full.clone(), true);
+        assert_eq!(hip.texture_dimensions, (4, 3));
+        assert_eq!((hip.image.width(), hip.image.height()), (2, 2));
+
+        let extra = hip.extra_header_data.as_ref().unwrap();
+        assert_eq!((extra.x_offset, extra.y_offset), (1, 1));
+
+        let BBCFHipImage::Indexed { data, .. } = hip.to_full_canvas() else {
+            panic!("expected an indexed image");
+        };
+        let BBCFHipImage::Indexed { data: original, .. } = &full else {
+            unreachable!()
+        };
+        assert_eq!(data.image, original.image);
+
+        let untrimmed = BBCFHip::from_full_canvas(full, false);
+        assert!(untrimmed.extra_header_data.is_none());
+        assert_eq!(untrimmed.image.width(), 4);
+
+        let empty = BBCFHipImage::Raw {
+            width: 0,
+            height: 0,
+            data: vec![],
+        };
+        let hip = BBCFHip::from_full_canvas(empty, true);
+        assert_eq!((hip.image.width(), hip.image.height()), (0, 0));
+
+        // offsets from a corrupt header that point past the canvas
+        let mut corrupt = BBCFHip::from_full_canvas(untrimmed.image.clone(), true);
+        corrupt.extra_header_data.as_mut().unwrap().x_offset = 100;
+        let canvas = corrupt.to_full_canvas();
+        assert_eq!((canvas.width(), canvas.height()), (4, 3));
+        assert_eq!(canvas.content_bounds(), None);
+    }
+}
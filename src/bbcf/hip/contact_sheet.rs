@@ -0,0 +1,316 @@
+use crate::{bbcf::hpl::BBCFHpl, helpers::RGBAColor, pac::Pac, Error};
+
+use super::{
+    sheet::{pac_sprites, DrawMode, Sheet},
+    BBCFHip, BBCFHipImage,
+};
+
+const BACKGROUND: RGBAColor = RGBAColor {
+    red: 0x20,
+    green: 0x20,
+    blue: 0x20,
+    alpha: 0xFF,
+};
+const TEXT: RGBAColor = RGBAColor {
+    red: 0xFF,
+    green: 0xFF,
+    blue: 0xFF,
+    alpha: 0xFF,
+};
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+/// Glyph plus the gap after it
+const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Room under each thumbnail for its label, with a pixel of padding above and below
+const LABEL_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// Lays the HIP sprites of a PAC out in a labeled grid of thumbnails
+pub struct ContactSheetBuilder {
+    cell_size: u32,
+    columns: u32,
+    palette: Option<Vec<RGBAColor>>,
+}
+
+impl Default for ContactSheetBuilder {
+    fn default() -> Self {
+        Self {
+            cell_size: 128,
+            columns: 8,
+            palette: None,
+        }
+    }
+}
+
+impl ContactSheetBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Width and height of a thumbnail, 128 by default.
+    /// Sprites are shrunk to fit, keeping their aspect ratio, but never enlarged
+    pub fn cell_size(mut self, cell_size: u32) -> Self {
+        self.cell_size = cell_size.max(1);
+        self
+    }
+
+    /// Thumbnails per row, 8 by default
+    pub fn columns(mut self, columns: u32) -> Self {
+        self.columns = columns.max(1);
+        self
+    }
+
+    /// Render indexed sprites with this palette instead of their own,
+    /// see [`BBCFHip::render_with_palette`]
+    pub fn palette(mut self, hpl: &BBCFHpl) -> Self {
+        self.palette = Some(hpl.palette.clone());
+        self
+    }
+
+    /// Render every HIP in the PAC into a single raw RGBA image,
+    /// with each entry's name (or hash if it has none) under its thumbnail
+    pub fn build(&self, pac: &Pac) -> Result<BBCFHipImage, Error> {
+        let mut cells = Vec::new();
+        for sprite in pac_sprites(pac) {
+            let (name, hip) = sprite?;
+            cells.push((name, self.thumbnail(&hip)?));
+        }
+
+        let columns = self.columns.min(cells.len().max(1) as u32);
+        let rows = (cells.len() as u32).div_ceil(columns).max(1);
+        let (cell_w, cell_h) = (self.cell_size, self.cell_size + LABEL_HEIGHT);
+
+        let mut sheet = Sheet::new(columns * cell_w, rows * cell_h, BACKGROUND);
+
+        for (i, (name, thumb)) in cells.iter().enumerate() {
+            let (cell_x, cell_y) = ((i as u32 % columns) * cell_w, (i as u32 / columns) * cell_h);
+
+            // centered horizontally, sitting on the label
+            let (x, y) = (
+                cell_x + (cell_w - thumb.width()) / 2,
+                cell_y + (self.cell_size - thumb.height()),
+            );
+            if let BBCFHipImage::Raw { width, data, .. } = thumb {
+                sheet.draw(data, *width, x, y, DrawMode::Blend);
+            }
+
+            let max_chars = ((cell_w - 1) / GLYPH_ADVANCE) as usize;
+            sheet.text(name, max_chars, cell_x + 1, cell_y + self.cell_size + 1);
+        }
+
+        Ok(sheet.into_image())
+    }
+
+    fn thumbnail(&self, hip: &BBCFHip) -> Result<BBCFHipImage, Error> {
+        let image = match (&self.palette, &hip.image) {
+            (Some(palette), BBCFHipImage::Indexed { .. }) => {
+                hip.image.render_with_palette(palette)?
+            }
+            _ => hip.image.to_raw(),
+        };
+
+        let (width, height) = (image.width(), image.height());
+        let largest = width.max(height);
+        if largest <= self.cell_size {
+            return Ok(image);
+        }
+
+        let scale = |len: u32| (len as u64 * self.cell_size as u64 / largest as u64).max(1) as u32;
+        Ok(downsample(
+            &image.rgba_pixels(),
+            width,
+            height,
+            scale(width),
+            scale(height),
+        ))
+    }
+}
+
+/// Box filter `pixels` down to `new_width` by `new_height`,
+/// weighting colors by their alpha so transparent pixels don't darken edges
+fn downsample(
+    pixels: &[RGBAColor],
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+) -> BBCFHipImage {
+    let span = |i: u32, len: u32, new_len: u32| {
+        let start = (i as u64 * len as u64 / new_len as u64) as u32;
+        let end = ((i as u64 + 1) * len as u64 / new_len as u64) as u32;
+        start..end.max(start + 1)
+    };
+
+    let mut data = Vec::with_capacity((new_width * new_height) as usize);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let (mut sum, mut alpha, mut count) = ([0u64; 3], 0u64, 0u64);
+
+            for sy in span(y, height, new_height) {
+                for sx in span(x, width, new_width) {
+                    let c = pixels[(sy * width + sx) as usize];
+                    let a = c.alpha as u64;
+                    sum[0] += c.red as u64 * a;
+                    sum[1] += c.green as u64 * a;
+                    sum[2] += c.blue as u64 * a;
+                    alpha += a;
+                    count += 1;
+                }
+            }
+
+            let channel = |s: u64| s.checked_div(alpha).unwrap_or(0) as u8;
+            data.push(RGBAColor {
+                red: channel(sum[0]),
+                green: channel(sum[1]),
+                blue: channel(sum[2]),
+                alpha: (alpha / count) as u8,
+            });
+        }
+    }
+
+    BBCFHipImage::Raw {
+        width: new_width,
+        height: new_height,
+        data,
+    }
+}
+
+impl Sheet {
+    /// Write `text` with the built-in font, cut off after `max_chars` characters
+    fn text(&mut self, text: &str, max_chars: usize, x: u32, y: u32) {
+        for (i, c) in text.chars().take(max_chars).enumerate() {
+            let left = x + i as u32 * GLYPH_ADVANCE;
+
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        let pos = (y + row as u32) * self.width + left + col;
+                        self.pixels[pos as usize] = TEXT;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A 3x5 glyph, one row per byte with the leftmost pixel in bit 2.
+/// Letters are drawn in uppercase and unknown characters as `?`
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [6, 1, 2, 4, 7],
+        '3' => [6, 1, 2, 1, 6],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 6, 1, 6],
+        '6' => [3, 4, 6, 5, 2],
+        '7' => [7, 1, 2, 2, 2],
+        '8' => [2, 5, 2, 5, 2],
+        '9' => [2, 5, 3, 1, 6],
+        '_' => [0, 0, 0, 0, 7],
+        '-' => [0, 0, 7, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ' ' => [0; 5],
+        _ => [6, 1, 2, 0, 2],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        helpers::IndexedImage,
+        pac::{Compression, PacEntry, PacStyle},
+    };
+
+    #[test]
+    fn test_contact_sheet() {
+        let palette = vec![
+            RGBAColor {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: 0,
+            },
+            RGBAColor {
+                red: 255,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            },
+        ];
+        let hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Indexed {
+                width: 16,
+                height: 8,
+                data: IndexedImage {
+                    palette,
+                    image: vec![1; 16 * 8],
+                },
+            },
+            false,
+        );
+        let hip = hip.to_bytes().unwrap();
+
+        let pac = Pac {
+            compression: Compression::None,
+            pac_style: PacStyle::NORMAL,
+            entries: vec![
+                PacEntry::new_named("a.hip".into(), hip.clone()),
+                PacEntry::new_named("b.hip".into(), hip),
+                PacEntry::new_named("not_a_sprite".into(), vec![0; 4]),
+            ],
+        };
+
+        let sheet = ContactSheetBuilder::new()
+            .cell_size(8)
+            .columns(4)
+            .build(&pac)
+            .unwrap();
+        // 2 cells side by side, each 8 wide and 8 + label tall
+        assert_eq!((sheet.width(), sheet.height()), (16, 8 + LABEL_HEIGHT));
+
+        // the 16x8 sprite shrinks to 8x4 at the bottom of the cell
+        let pixels = sheet.rgba_pixels();
+        assert_eq!(pixels[3 * 16], BACKGROUND);
+        assert_eq!(pixels[4 * 16].red, 255);
+
+        let hpl = BBCFHpl {
+            version: 0,
+            header: Default::default(),
+            palette: vec![TEXT; 2],
+        };
+        let recolored = ContactSheetBuilder::new()
+            .cell_size(8)
+            .palette(&hpl)
+            .build(&pac)
+            .unwrap();
+        assert_eq!(recolored.rgba_pixels()[4 * 16], TEXT);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{
+    helpers::{IndexedImage, RGBAColor},
+    Error,
+};
+
+use super::{render::color_lookup, BBCFHipImage, LumaPixel, TRANSPARENT};
+
+/// A single pixel of a [`BBCFHipImage`], matching the image's variant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HipPixel {
+    Index(u8),
+    Color(RGBAColor),
+    Luma(u16),
+}
+
+impl BBCFHipImage {
+    /// The pixel at `(x, y)`, or `None` if it's outside of the image
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<HipPixel> {
+        let i = self.pixel_index(x, y)?;
+
+        match self {
+            BBCFHipImage::Indexed { data, .. } => data.image.get(i).copied().map(HipPixel::Index),
+            BBCFHipImage::Raw { data, .. } => data.get(i).copied().map(HipPixel::Color),
+            BBCFHipImage::Luma { data, .. } => data.get(i).copied().map(HipPixel::Luma),
+        }
+    }
+
+    /// Overwrite the pixel at `(x, y)`. The pixel has to match the image's variant
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: HipPixel) -> Result<(), Error> {
+        let i = self.pixel_index(x, y).ok_or_else(|| {
+            Error::Rebuilder(format!(
+                "pixel ({x}, {y}) is outside of a {}x{} image",
+                self.width(),
+                self.height()
+            ))
+        })?;
+
+        let slot = match (self, pixel) {
+            (BBCFHipImage::Indexed { data, .. }, HipPixel::Index(index)) => {
+                data.image.get_mut(i).map(|p| *p = index)
+            }
+            (BBCFHipImage::Raw { data, .. }, HipPixel::Color(color)) => {
+                data.get_mut(i).map(|p| *p = color)
+            }
+            (BBCFHipImage::Luma { data, .. }, HipPixel::Luma(luma)) => {
+                data.get_mut(i).map(|p| *p = luma)
+            }
+            (_, pixel) => {
+                return Err(Error::Rebuilder(format!(
+                    "{pixel:?} doesn't match the image type"
+                )))
+            }
+        };
+
+        slot.ok_or_else(|| Error::Rebuilder("image data is shorter than its dimensions".into()))
+    }
+
+    /// Resize the canvas to `width` by `height`, placing the current image at `(x, y)`.
+    /// Anything outside of the new canvas is clipped, new pixels are transparent,
+    /// index 0 or zero luma depending on the image type
+    pub fn resize_canvas(&mut self, width: u32, height: u32, x: u32, y: u32) {
+        let placement = Placement {
+            width: self.width(),
+            height: self.height(),
+            canvas_width: width,
+            canvas_height: height,
+            x,
+            y,
+        };
+
+        *self = self.map_pixels(width, height, &placement);
+    }
+
+    /// Mirror the image left to right
+    pub fn flip_horizontal(&mut self) {
+        let flip = Flip {
+            width: self.width(),
+            horizontal: true,
+        };
+
+        *self = self.map_pixels(self.width(), self.height(), &flip);
+    }
+
+    /// Mirror the image top to bottom
+    pub fn flip_vertical(&mut self) {
+        let flip = Flip {
+            width: self.width(),
+            horizontal: false,
+        };
+
+        *self = self.map_pixels(self.width(), self.height(), &flip);
+    }
+
+    /// Copy the `width` by `height` area at `(x, y)` into a new image of the same type
+    pub fn sub_image(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self, Error> {
+        let fits =
+            |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+        if !fits(x, width, self.width()) || !fits(y, height, self.height()) {
+            return Err(Error::Rebuilder(format!(
+                "{width}x{height} area at ({x}, {y}) is outside of a {}x{} image",
+                self.width(),
+                self.height()
+            )));
+        }
+
+        let crop = Crop {
+            width: self.width(),
+            x,
+            y,
+            w: width,
+            h: height,
+        };
+
+        Ok(self.map_pixels(width, height, &crop))
+    }
+
+    /// Convert to a raw RGBA image of the same size.
+    ///
+    /// Indexed pixels are drawn the same way as [`BBCFHipImage::render_with_palette`] with their own palette,
+    /// so index 0 and indexes past its end are transparent.
+    /// Luma pixels become gray colors, see [`LumaPixel`]
+    pub fn to_raw(&self) -> BBCFHipImage {
+        let data = match self {
+            BBCFHipImage::Indexed { data, .. } => {
+                let lookup = color_lookup(&data.palette);
+                data.image.iter().map(|&i| lookup[i as usize]).collect()
+            }
+            BBCFHipImage::Raw { data, .. } => data.clone(),
+            BBCFHipImage::Luma { data, .. } => {
+                data.iter().map(|&l| LumaPixel::from(l).into()).collect()
+            }
+        };
+
+        BBCFHipImage::Raw {
+            width: self.width(),
+            height: self.height(),
+            data,
+        }
+    }
+
+    fn pixel_index(&self, x: u32, y: u32) -> Option<usize> {
+        (x < self.width() && y < self.height())
+            .then(|| y as usize * self.width() as usize + x as usize)
+    }
+
+    /// Apply a layout change to the pixels of any image type, giving an image of `width` by `height`
+    pub(super) fn map_pixels(&self, width: u32, height: u32, map: &impl PixelMap) -> Self {
+        match self {
+            BBCFHipImage::Indexed { data, .. } => BBCFHipImage::Indexed {
+                width,
+                height,
+                data: IndexedImage {
+                    palette: data.palette.clone(),
+                    image: map.apply(&data.image, 0),
+                },
+            },
+            BBCFHipImage::Raw { data, .. } => BBCFHipImage::Raw {
+                width,
+                height,
+                data: map.apply(data, TRANSPARENT),
+            },
+            BBCFHipImage::Luma { data, .. } => BBCFHipImage::Luma {
+                width,
+                height,
+                data: map.apply(data, 0),
+            },
+        }
+    }
+}
+
+/// A change in pixel layout that works the same way for every image type
+pub(super) trait PixelMap {
+    /// Build the new pixels from `data`, using `fill` for pixels with no source
+    fn apply<T: Copy>(&self, data: &[T], fill: T) -> Vec<T>;
+}
+
+pub(super) struct Crop {
+    pub width: u32,
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl PixelMap for Crop {
+    /// Copy the area out of `data`, rows that fall outside of it are filled with `fill`
+    fn apply<T: Copy>(&self, data: &[T], fill: T) -> Vec<T> {
+        let (width, w) = (self.width as usize, self.w as usize);
+        let fits = self.x as usize + w <= width;
+
+        let mut pixels = Vec::with_capacity(w * self.h as usize);
+        for row in self.y as usize..self.y as usize + self.h as usize {
+            let start = row * width + self.x as usize;
+
+            match data.get(start..start + w).filter(|_| fits) {
+                Some(row) => pixels.extend_from_slice(row),
+                None => pixels.resize(pixels.len() + w, fill),
+            }
+        }
+
+        pixels
+    }
+}
+
+pub(super) struct Placement {
+    pub width: u32,
+    pub height: u32,
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl PixelMap for Placement {
+    /// Copy `data` onto an empty canvas, clipping anything outside of it.
+    /// Rows missing from `data` are left filled with `fill`
+    fn apply<T: Copy>(&self, data: &[T], fill: T) -> Vec<T> {
+        let canvas_width = self.canvas_width as usize;
+        let mut canvas = vec![fill; canvas_width * self.canvas_height as usize];
+
+        let visible_w = self.width.min(self.canvas_width.saturating_sub(self.x)) as usize;
+        let visible_h = self.height.min(self.canvas_height.saturating_sub(self.y)) as usize;
+        if visible_w == 0 || visible_h == 0 {
+            return canvas;
+        }
+
+        for row in 0..visible_h {
+            let src = row * self.width as usize;
+            let dst = (self.y as usize + row) * canvas_width + self.x as usize;
+
+            if let Some(row) = data.get(src..src + visible_w) {
+                canvas[dst..dst + visible_w].copy_from_slice(row);
+            }
+        }
+
+        canvas
+    }
+}
+
+struct Flip {
+    width: u32,
+    horizontal: bool,
+}
+
+impl PixelMap for Flip {
+    fn apply<T: Copy>(&self, data: &[T], _fill: T) -> Vec<T> {
+        let rows = data.chunks(self.width.max(1) as usize);
+
+        if self.horizontal {
+            rows.flat_map(|row| row.iter().rev().copied()).collect()
+        } else {
+            rows.rev().flatten().copied().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_editing() {
+        // 3x2 image, indexes 0..6 row by row
+        let mut image = BBCFHipImage::Indexed {
+            width: 3,
+            height: 2,
+            data: IndexedImage {
+                palette: vec![TRANSPARENT; 6],
+                image: (0..6).collect(),
+            },
+        };
+
+        assert_eq!(image.get_pixel(2, 1), Some(HipPixel::Index(5)));
+        assert_eq!(image.get_pixel(3, 0), None);
+
+        image.set_pixel(0, 0, HipPixel::Index(9)).unwrap();
+        assert!(image.set_pixel(0, 0, HipPixel::Luma(1)).is_err());
+        assert!(image.set_pixel(0, 2, HipPixel::Index(1)).is_err());
+
+        image.flip_horizontal();
+        assert_eq!(image.get_pixel(0, 0), Some(HipPixel::Index(2)));
+        image.flip_vertical();
+        assert_eq!(image.get_pixel(0, 0), Some(HipPixel::Index(5)));
+        assert_eq!(image.get_pixel(2, 1), Some(HipPixel::Index(9)));
+
+        let sub = image.sub_image(1, 1, 2, 1).unwrap();
+        assert_eq!((sub.width(), sub.height()), (2, 1));
+        assert_eq!(sub.get_pixel(1, 0), Some(HipPixel::Index(9)));
+        assert!(image.sub_image(2, 0, 2, 1).is_err());
+
+        image.resize_canvas(4, 4, 1, 2);
+        assert_eq!((image.width(), image.height()), (4, 4));
+        assert_eq!(image.get_pixel(0, 0), Some(HipPixel::Index(0)));
+        assert_eq!(image.get_pixel(3, 3), Some(HipPixel::Index(9)));
+
+        // placed entirely outside of the new canvas
+        let mut outside = image.clone();
+        outside.resize_canvas(4, 4, 100, 0);
+        assert_eq!(outside.get_pixel(3, 3), Some(HipPixel::Index(0)));
+        outside.resize_canvas(2, 2, 0, 2);
+        assert_eq!((outside.width(), outside.height()), (2, 2));
+
+        // data shorter than the size says, the missing rows stay transparent
+        let color = RGBAColor {
+            red: 1,
+            green: 2,
+            blue: 3,
+            alpha: 4,
+        };
+        let mut short = BBCFHipImage::Raw {
+            width: 2,
+            height: 2,
+            data: vec![color; 3],
+        };
+        short.resize_canvas(3, 3, 1, 1);
+        assert_eq!(short.get_pixel(1, 1), Some(HipPixel::Color(color)));
+        assert_eq!(short.get_pixel(1, 2), Some(HipPixel::Color(TRANSPARENT)));
+
+        let luma = BBCFHipImage::Luma {
+            width: 1,
+            height: 1,
+            data: vec![0x80_40],
+        };
+        assert_eq!(
+            luma.to_raw().get_pixel(0, 0),
+            Some(HipPixel::Color(RGBAColor {
+                red: 0x40,
+                green: 0x40,
+                blue: 0x40,
+                alpha: 0x80,
+            }))
+        );
+    }
+}
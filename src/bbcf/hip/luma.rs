@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::RGBAColor;
+
+use super::BBCFHipImage;
+
+/// A pixel of a [`BBCFHipImage::Luma`] image.
+///
+/// The 16 bits are assumed to be 8-bit gray in the low byte and 8-bit alpha in the high byte,
+/// this hasn't been checked against the game. Converting between the two
+/// representations is lossless in both directions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LumaPixel {
+    pub luma: u8,
+    pub alpha: u8,
+}
+
+impl From<u16> for LumaPixel {
+    fn from(value: u16) -> Self {
+        let [luma, alpha] = value.to_le_bytes();
+        Self { luma, alpha }
+    }
+}
+
+impl From<LumaPixel> for u16 {
+    fn from(pixel: LumaPixel) -> Self {
+        u16::from_le_bytes([pixel.luma, pixel.alpha])
+    }
+}
+
+impl From<LumaPixel> for RGBAColor {
+    fn from(pixel: LumaPixel) -> Self {
+        RGBAColor {
+            red: pixel.luma,
+            green: pixel.luma,
+            blue: pixel.luma,
+            alpha: pixel.alpha,
+        }
+    }
+}
+
+impl From<RGBAColor> for LumaPixel {
+    /// Gray colors convert back exactly, anything else is weighted with the BT.601 coefficients
+    fn from(color: RGBAColor) -> Self {
+        let weighted = 299 * color.red as u32 + 587 * color.green as u32 + 114 * color.blue as u32;
+
+        Self {
+            luma: ((weighted + 500) / 1000) as u8,
+            alpha: color.alpha,
+        }
+    }
+}
+
+impl BBCFHipImage {
+    /// Build a luma image from gray and alpha pixels
+    pub fn from_luma_pixels(width: u32, height: u32, pixels: &[LumaPixel]) -> Self {
+        BBCFHipImage::Luma {
+            width,
+            height,
+            data: pixels.iter().map(|&p| p.into()).collect(),
+        }
+    }
+
+    /// The gray and alpha of every pixel, or `None` if this isn't a luma image
+    pub fn luma_pixels(&self) -> Option<Vec<LumaPixel>> {
+        match self {
+            BBCFHipImage::Luma { data, .. } => Some(data.iter().map(|&l| l.into()).collect()),
+            _ => None,
+        }
+    }
+
+    /// Convert to a luma image of the same size, see [`LumaPixel`] for how colors are mapped.
+    /// Indexed pixels are looked up in their palette first
+    pub fn to_luma(&self) -> BBCFHipImage {
+        if let BBCFHipImage::Luma { .. } = self {
+            return self.clone();
+        }
+
+        let BBCFHipImage::Raw {
+            width,
+            height,
+            data,
+        } = self.to_raw()
+        else {
+            unreachable!("to_raw always gives a raw image")
+        };
+
+        BBCFHipImage::Luma {
+            width,
+            height,
+            data: data
+                .into_iter()
+                .map(|c| LumaPixel::from(c).into())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luma_conversions() {
+        let pixel = LumaPixel::from(0x80_40);
+        assert_eq!((pixel.luma, pixel.alpha), (0x40, 0x80));
+        assert_eq!(u16::from(pixel), 0x80_40);
+
+        // every value survives a trip through RGBA
+        for value in 0..=u16::MAX {
+            let color = RGBAColor::from(LumaPixel::from(value));
+            assert_eq!(u16::from(LumaPixel::from(color)), value);
+        }
+
+        let raw = BBCFHipImage::Raw {
+            width: 2,
+            height: 1,
+            data: vec![
+                RGBAColor {
+                    red: 255,
+                    green: 0,
+                    blue: 0,
+                    alpha: 255,
+                },
+                RGBAColor {
+                    red: 10,
+                    green: 10,
+                    blue: 10,
+                    alpha: 0,
+                },
+            ],
+        };
+        let luma = raw.to_luma().luma_pixels().unwrap();
+        assert_eq!(
+            luma[0],
+            LumaPixel {
+                luma: 76,
+                alpha: 255
+            }
+        );
+        assert_eq!(luma[1], LumaPixel { luma: 10, alpha: 0 });
+    }
+}
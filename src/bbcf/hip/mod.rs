@@ -0,0 +1,728 @@
+use std::io::{Cursor, Read, Seek, Write};
+
+use crate::{
+    helpers::{self, IndexedImage, RGBAColor},
+    traits::Palette,
+    Error,
+};
+
+use binrw::{
+    binrw,
+    meta::{EndianKind, ReadEndian, WriteEndian},
+    BinRead, BinResult, BinWrite, Endian,
+};
+use serde::{Deserialize, Serialize};
+
+use self::rle::{RunSplit, RunValue};
+
+mod atlas;
+mod canvas;
+mod contact_sheet;
+mod edit;
+mod luma;
+mod png;
+mod quantize;
+mod render;
+pub mod rle;
+mod sheet;
+mod stream;
+mod verify;
+
+pub use self::atlas::{Atlas, AtlasBuilder, AtlasMetadata, AtlasSprite};
+pub use self::contact_sheet::ContactSheetBuilder;
+pub use self::edit::HipPixel;
+pub use self::luma::LumaPixel;
+pub use self::png::{DEFAULT_VERSION, PNG_METADATA_KEYWORD};
+pub use self::quantize::{median_cut, Dithering};
+pub use self::render::{render_all, Recolor};
+pub use self::stream::{HipPixels, HipRows, HipStream};
+pub use self::verify::{verify_roundtrip, RoundtripFailure, RoundtripFailureKind, RoundtripReport};
+
+/// Fill color for pixels not covered by any image data
+const TRANSPARENT: RGBAColor = RGBAColor {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 0,
+};
+
+/// A contained buffer of pixel (and possibly palette) data
+/// stored within a [`BBCFHip`]
+#[derive(Clone, Serialize, Deserialize)]
+pub enum BBCFHipImage {
+    Indexed {
+        #[serde(skip)]
+        width: u32,
+        #[serde(skip)]
+        height: u32,
+        #[serde(skip)]
+        data: IndexedImage,
+    },
+    /// A raw RGBA image
+    Raw {
+        #[serde(skip)]
+        width: u32,
+        #[serde(skip)]
+        height: u32,
+        #[serde(skip)]
+        data: Vec<RGBAColor>,
+    },
+    /// 16-bit pixels, assumed to hold gray and alpha, see [`LumaPixel`]
+    Luma {
+        #[serde(skip)]
+        width: u32,
+        #[serde(skip)]
+        height: u32,
+        #[serde(skip)]
+        data: Vec<u16>,
+    },
+}
+
+impl BBCFHipImage {
+    pub fn width(&self) -> u32 {
+        match self {
+            BBCFHipImage::Indexed { width, .. } => *width,
+            BBCFHipImage::Raw { width, .. } => *width,
+            BBCFHipImage::Luma { width, .. } => *width,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            BBCFHipImage::Indexed { height, .. } => *height,
+            BBCFHipImage::Raw { height, .. } => *height,
+            BBCFHipImage::Luma { height, .. } => *height,
+        }
+    }
+
+    /// Every pixel as RGBA, see [`BBCFHipImage::to_raw`]
+    fn rgba_pixels(&self) -> Vec<RGBAColor> {
+        match self.to_raw() {
+            BBCFHipImage::Raw { data, .. } => data,
+            _ => unreachable!("to_raw always gives a raw image"),
+        }
+    }
+}
+
+/// The sprite format used in Blazblue Centralfiction
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BBCFHip {
+    /// Assumed to be a format version, [`DEFAULT_VERSION`] in every file seen so far
+    pub version: u32,
+    /// The full width and full height don't necessarily indicate
+    /// actual image dimensions, they seem to be used to indicate texture
+    /// size, and the extra header data added on is used to indicate actual
+    /// dimensions for processing the image data
+    pub texture_dimensions: (u32, u32),
+    pub flags: BBCFHipFlags,
+    pub extra_header_data: Option<BBCFHipExtra>,
+    pub image: BBCFHipImage,
+    #[serde(default)]
+    pub quirks: BBCFHipQuirks,
+}
+
+/// Details of how the original file was encoded,
+/// kept so that an unmodified [`BBCFHip`] rebuilds byte-for-byte
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BBCFHipQuirks {
+    /// Where the original encoder broke up runs of identical pixels
+    pub run_split: RunSplit,
+    /// Bytes after the image data that are still counted in the header's file size
+    pub padding: Vec<u8>,
+    /// Null bytes after the end of the file given by the header, a couple of files end with 2 of these.
+    /// Only read by [`BBCFHip::parse`] and written by [`BBCFHip::to_bytes`]
+    pub trailing: Vec<u8>,
+}
+
+/// The 3 bytes following the encoding tag in the header, not decoded yet.
+/// [`RoundtripReport::header_values`] surveys them over a folder of HIPs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BBCFHipFlags {
+    /// At 0x19, right after the encoding tag
+    pub unknown_19: u8,
+    /// At 0x1A
+    pub unknown_1a: u8,
+    /// At 0x1B, the last byte before the extra header size
+    pub unknown_1b: u8,
+}
+
+impl From<[u8; 3]> for BBCFHipFlags {
+    fn from(bytes: [u8; 3]) -> Self {
+        Self {
+            unknown_19: bytes[0],
+            unknown_1a: bytes[1],
+            unknown_1b: bytes[2],
+        }
+    }
+}
+
+impl From<BBCFHipFlags> for [u8; 3] {
+    fn from(flags: BBCFHipFlags) -> Self {
+        [flags.unknown_19, flags.unknown_1a, flags.unknown_1b]
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BBCFHipExtra {
+    pub x_offset: u32,
+    pub y_offset: u32,
+    /// The 16 bytes after the offsets, missing if the extra header is too short to hold them
+    pub details: Option<BBCFHipExtraDetails>,
+    /// Any bytes past the known fields
+    pub extra: Vec<u8>,
+}
+
+/// The 4 words after the offsets that fill out the usual 0x20 byte extra header.
+/// Written back as they were read until game files show what they hold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BBCFHipExtraDetails {
+    /// At 0x30, right after the Y offset
+    pub unknown_30: u32,
+    /// At 0x34
+    pub unknown_34: u32,
+    /// At 0x38
+    pub unknown_38: u32,
+    /// At 0x3C, the end of the usual extra header
+    pub unknown_3c: u32,
+}
+
+impl BBCFHipExtraDetails {
+    const SIZE: usize = 0x10;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        Self {
+            unknown_30: u32_at(0x0),
+            unknown_34: u32_at(0x4),
+            unknown_38: u32_at(0x8),
+            unknown_3c: u32_at(0xC),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let values = [
+            self.unknown_30,
+            self.unknown_34,
+            self.unknown_38,
+            self.unknown_3c,
+        ];
+
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        bytes
+    }
+}
+
+impl BBCFHipExtra {
+    /// Create an extra header with the offsets and zeroed details,
+    /// matching the layout of most files
+    pub fn new(x_offset: u32, y_offset: u32) -> Self {
+        Self {
+            x_offset,
+            y_offset,
+            details: Some(BBCFHipExtraDetails::default()),
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        let details_size = self.details.map_or(0, |_| BBCFHipExtraDetails::SIZE);
+        EXTRA_HEADER_FIXED_SIZE + (details_size + self.extra.len()) as u32
+    }
+}
+
+helpers::impl_open!(BBCFHip);
+
+// HIP file structure
+//
+// 00: "HIP\0" magic string
+// 04: version?
+// 08: file size
+// 0C: palette size
+// 10: texture width
+// 14: texture height
+// 18: 1-byte encoding tag, 3 unknown bytes (see `BBCFHipFlags`)
+// 1C: extra header data size
+// 20..N (if extra header data):
+// image width
+// image height
+// X offset
+// Y offset
+// 16 unknown bytes (see `BBCFHipExtraDetails`)
+// anything else up to the extra header data size
+//
+// end of header..N: palette of BGRA8 colors (indexed only), then run-length encoded image data
+//
+// Encoding tag values: 0x01 for indexed, 0x10 for ARGB, 0x04 for luma (16-bit, see `LumaPixel`)
+const HEADER_SIZE: u32 = 0x20;
+const EXTRA_HEADER_FIXED_SIZE: u32 = 0x10;
+
+const ENCODING_INDEXED: u8 = 0x01;
+const ENCODING_LUMA: u8 = 0x04;
+const ENCODING_ARGB: u8 = 0x10;
+
+#[binrw]
+#[brw(magic = b"HIP\0", little)]
+struct HipHeader {
+    version: u32,
+    file_size: u32,
+    palette_size: u32,
+    texture_width: u32,
+    texture_height: u32,
+    encoding: u8,
+    flags: [u8; 3],
+    extra_header_size: u32,
+}
+
+#[binrw]
+#[brw(little)]
+#[br(import(size: u32))]
+struct ExtraHeader {
+    width: u32,
+    height: u32,
+    x_offset: u32,
+    y_offset: u32,
+    #[br(count = size - EXTRA_HEADER_FIXED_SIZE)]
+    extra: Vec<u8>,
+}
+
+/// Everything in a HIP before the run-length data
+struct HipHead {
+    header: HipHeader,
+    width: u32,
+    height: u32,
+    extra_header_data: Option<BBCFHipExtra>,
+    palette: Vec<RGBAColor>,
+}
+
+impl HipHead {
+    fn read<R: Read + Seek>(reader: &mut R) -> BinResult<Self> {
+        let header = HipHeader::read(reader)?;
+
+        let mut width = header.texture_width;
+        let mut height = header.texture_height;
+
+        let extra_header_data = if header.extra_header_size >= EXTRA_HEADER_FIXED_SIZE {
+            let extra = ExtraHeader::read_args(reader, (header.extra_header_size,))?;
+
+            width = extra.width;
+            height = extra.height;
+
+            let (details, rest) = if extra.extra.len() >= BBCFHipExtraDetails::SIZE {
+                let (details, rest) = extra.extra.split_at(BBCFHipExtraDetails::SIZE);
+                (Some(BBCFHipExtraDetails::from_bytes(details)), rest)
+            } else {
+                (None, extra.extra.as_slice())
+            };
+
+            Some(BBCFHipExtra {
+                x_offset: extra.x_offset,
+                y_offset: extra.y_offset,
+                details,
+                extra: rest.to_vec(),
+            })
+        } else {
+            None
+        };
+
+        let palette = if header.encoding == ENCODING_INDEXED {
+            // read through `take` so a corrupt size can't allocate past the end of the data
+            let pos = reader.stream_position()?;
+            let size = header.palette_size as u64 * 4;
+            let mut bytes = Vec::new();
+            reader.by_ref().take(size).read_to_end(&mut bytes)?;
+
+            if bytes.len() as u64 != size {
+                return Err(binrw::Error::AssertFail {
+                    pos,
+                    message: format!(
+                        "palette of {} colors ends after {} bytes",
+                        header.palette_size,
+                        bytes.len()
+                    ),
+                });
+            }
+
+            bytes
+                .chunks_exact(4)
+                .map(|c| RGBAColor {
+                    red: c[2],
+                    green: c[1],
+                    blue: c[0],
+                    alpha: c[3],
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            header,
+            width,
+            height,
+            extra_header_data,
+            palette,
+        })
+    }
+}
+
+impl BinRead for BBCFHip {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        let start = reader.stream_position()?;
+        let HipHead {
+            header,
+            width,
+            height,
+            extra_header_data,
+            palette,
+        } = HipHead::read(reader)?;
+
+        // read the rest of the file in one go, the runs are decoded from memory
+        let data_start = reader.stream_position()?;
+        let data_size = (start + header.file_size as u64).saturating_sub(data_start);
+        let mut data = Vec::new();
+        reader.by_ref().take(data_size).read_to_end(&mut data)?;
+
+        let to_binrw_error = |e: Error| binrw::Error::AssertFail {
+            pos: data_start,
+            message: e.to_string(),
+        };
+        let len = width
+            .checked_mul(height)
+            .map(|len| len as usize)
+            .ok_or_else(|| Error::Parser(format!("{width}x{height} image is too large")))
+            .map_err(to_binrw_error)?;
+
+        let (image, consumed, run_split) = match header.encoding {
+            ENCODING_INDEXED => {
+                let (image, consumed, run_split) =
+                    decode_image(&data, width, len).map_err(to_binrw_error)?;
+                let image = BBCFHipImage::Indexed {
+                    width,
+                    height,
+                    data: IndexedImage { palette, image },
+                };
+
+                (image, consumed, run_split)
+            }
+            ENCODING_ARGB => {
+                let (data, consumed, run_split) =
+                    decode_image(&data, width, len).map_err(to_binrw_error)?;
+                let image = BBCFHipImage::Raw {
+                    width,
+                    height,
+                    data,
+                };
+
+                (image, consumed, run_split)
+            }
+            ENCODING_LUMA => {
+                let (data, consumed, run_split) =
+                    decode_image(&data, width, len).map_err(to_binrw_error)?;
+                let image = BBCFHipImage::Luma {
+                    width,
+                    height,
+                    data,
+                };
+
+                (image, consumed, run_split)
+            }
+            encoding => {
+                return Err(binrw::Error::AssertFail {
+                    pos: start + 0x18,
+                    message: format!("unknown HIP encoding {encoding:#x}"),
+                })
+            }
+        };
+
+        let quirks = BBCFHipQuirks {
+            run_split,
+            padding: data[consumed..].to_vec(),
+            trailing: Vec::new(),
+        };
+
+        Ok(BBCFHip {
+            version: header.version,
+            texture_dimensions: (header.texture_width, header.texture_height),
+            flags: header.flags.into(),
+            extra_header_data,
+            image,
+            quirks,
+        })
+    }
+}
+
+impl ReadEndian for BBCFHip {
+    const ENDIAN: EndianKind = EndianKind::Endian(Endian::Little);
+}
+
+impl BinWrite for BBCFHip {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        _endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        let split = self.quirks.run_split;
+        let width = self.image.width() as usize;
+
+        let mut image_bytes = Vec::new();
+        let (encoding, palette_size) = match &self.image {
+            BBCFHipImage::Indexed { data, .. } => {
+                let palette = data.get_palette();
+                image_bytes.extend(palette.iter().flat_map(|c| c.to_bgra_slice()));
+                rle::encode_runs_split(&data.image, width, split, &mut image_bytes);
+
+                (ENCODING_INDEXED, palette.len() as u32)
+            }
+            BBCFHipImage::Raw { data, .. } => {
+                rle::encode_runs_split(data, width, split, &mut image_bytes);
+                (ENCODING_ARGB, 0)
+            }
+            BBCFHipImage::Luma { data, .. } => {
+                rle::encode_runs_split(data, width, split, &mut image_bytes);
+                (ENCODING_LUMA, 0)
+            }
+        };
+        image_bytes.extend(&self.quirks.padding);
+
+        let extra_header_size = self.extra_header_data.as_ref().map_or(0, |x| x.size());
+
+        // without an extra header the texture size is all that gives the image size
+        let (texture_width, texture_height) = match self.extra_header_data {
+            Some(_) => self.texture_dimensions,
+            None => (self.image.width(), self.image.height()),
+        };
+
+        HipHeader {
+            version: self.version,
+            file_size: HEADER_SIZE + extra_header_size + image_bytes.len() as u32,
+            palette_size,
+            texture_width,
+            texture_height,
+            encoding,
+            flags: self.flags.into(),
+            extra_header_size,
+        }
+        .write(writer)?;
+
+        if let Some(extra) = &self.extra_header_data {
+            let mut rest = extra
+                .details
+                .map(|d| d.to_bytes().to_vec())
+                .unwrap_or_default();
+            rest.extend(&extra.extra);
+
+            ExtraHeader {
+                width: self.image.width(),
+                height: self.image.height(),
+                x_offset: extra.x_offset,
+                y_offset: extra.y_offset,
+                extra: rest,
+            }
+            .write(writer)?;
+        }
+
+        writer.write_all(&image_bytes)?;
+
+        Ok(())
+    }
+}
+
+impl WriteEndian for BBCFHip {
+    const ENDIAN: EndianKind = EndianKind::Endian(Endian::Little);
+}
+
+impl BBCFHip {
+    pub fn parse(input: &[u8]) -> Result<BBCFHip, Error> {
+        let mut cursor = Cursor::new(input);
+        let mut hip = BBCFHip::read(&mut cursor).map_err(|e| Error::Parser(e.to_string()))?;
+
+        let rest = &input[cursor.position() as usize..];
+
+        // a couple of files have null bytes past the end given by the header,
+        // they're kept around so the file can be rebuilt as it was
+        if rest.iter().all(|b| *b == 0) {
+            hip.quirks.trailing = rest.to_vec();
+            return Ok(hip);
+        }
+
+        helpers::slice_consumed(rest)?;
+        Ok(hip)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut cursor = Cursor::new(Vec::new());
+        self.write(&mut cursor)
+            .map_err(|e| Error::Rebuilder(e.to_string()))?;
+
+        let mut bytes = cursor.into_inner();
+        bytes.extend(&self.quirks.trailing);
+
+        Ok(bytes)
+    }
+}
+
+fn decode_image<T: RunValue>(
+    data: &[u8],
+    width: u32,
+    len: usize,
+) -> Result<(Vec<T>, usize, RunSplit), Error> {
+    let (pixels, consumed) = rle::decode_runs(data, len)?;
+
+    // runs split some other way are re-encoded with the default split
+    let run_split = rle::detect_run_split::<T>(data, width as usize, len).unwrap_or_default();
+
+    Ok((pixels, consumed, run_split))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hip_roundtrip() {
+        let hip = BBCFHip {
+            version: DEFAULT_VERSION,
+            texture_dimensions: (8, 8),
+            flags: BBCFHipFlags::default(),
+            extra_header_data: Some(BBCFHipExtra::new(3, 4)),
+            image: BBCFHipImage::Indexed {
+                width: 2,
+                height: 2,
+                data: IndexedImage {
+                    palette: vec![RGBAColor {
+                        red: 1,
+                        green: 2,
+                        blue: 3,
+                        alpha: 4,
+                    }],
+                    image: vec![0; 4],
+                },
+            },
+            quirks: Default::default(),
+        };
+
+        let bytes = hip.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 0x20 + 0x20 + 4 + 2);
+        assert_eq!(&bytes[0x40..0x44], &[3, 2, 1, 4]);
+        assert_eq!(&bytes[0x44..], &[0, 4]);
+
+        let parsed = BBCFHip::parse(&bytes).unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+        // usable as a field of other binrw types
+        #[binrw::binread]
+        #[br(little)]
+        struct Wrapper {
+            _count: u32,
+            hip: BBCFHip,
+        }
+
+        let mut wrapped = 1u32.to_le_bytes().to_vec();
+        wrapped.extend(&bytes);
+        let wrapper = Wrapper::read(&mut Cursor::new(wrapped)).unwrap();
+        assert_eq!(wrapper.hip.image.width(), 2);
+
+        // an edited image without an extra header keeps its new size
+        let mut hip = BBCFHip::from_full_canvas(hip.image, false);
+        hip.image.resize_canvas(8, 8, 0, 0);
+        let parsed = BBCFHip::parse(&hip.to_bytes().unwrap()).unwrap();
+        assert_eq!((parsed.image.width(), parsed.image.height()), (8, 8));
+        assert_eq!(parsed.texture_dimensions, (8, 8));
+        assert!(parsed.quirks.padding.is_empty());
+
+        // a corrupt size whose pixel count overflows a u32
+        let mut huge = bytes.clone();
+        huge[0x20..0x28].copy_from_slice(&[0xFF; 8]);
+        assert!(matches!(BBCFHip::parse(&huge), Err(Error::Parser(e)) if e.contains("too large")));
+
+        // a corrupt palette size is limited by the data that's actually there
+        let mut huge = bytes.clone();
+        huge[0x0C..0x10].copy_from_slice(&[0xFF; 4]);
+        assert!(matches!(BBCFHip::parse(&huge), Err(Error::Parser(e)) if e.contains("palette")));
+    }
+
+    #[test]
+    fn test_hip_quirks() {
+        let mut hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Luma {
+                width: 2,
+                height: 2,
+                data: vec![7; 4],
+            },
+            false,
+        );
+        hip.quirks = BBCFHipQuirks {
+            run_split: RunSplit::Rows,
+            padding: vec![0; 2],
+            trailing: vec![0; 2],
+        };
+
+        let bytes = hip.to_bytes().unwrap();
+        // 2 runs of 2, padding counted in the file size, trailing bytes after it
+        assert_eq!(&bytes[0x20..], &[7, 0, 2, 7, 0, 2, 0, 0, 0, 0]);
+        assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 0x28);
+
+        let parsed = BBCFHip::parse(&bytes).unwrap();
+        assert_eq!(parsed.quirks, hip.quirks);
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_hip_extra_details() {
+        let mut hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Luma {
+                width: 1,
+                height: 1,
+                data: vec![1],
+            },
+            true,
+        );
+        hip.flags = BBCFHipFlags::from([1, 2, 3]);
+
+        let extra = hip.extra_header_data.as_mut().unwrap();
+        extra.details = Some(BBCFHipExtraDetails {
+            unknown_30: 5,
+            unknown_34: 6,
+            unknown_38: 0,
+            unknown_3c: 7,
+        });
+        extra.extra = vec![8; 8];
+
+        let bytes = hip.to_bytes().unwrap();
+        assert_eq!(&bytes[0x19..0x1C], &[1, 2, 3]);
+        assert_eq!(bytes[0x1C], 0x28);
+        assert_eq!(&bytes[0x30..0x34], &5u32.to_le_bytes());
+
+        let parsed = BBCFHip::parse(&bytes).unwrap();
+        assert_eq!(parsed.flags.unknown_1a, 2);
+        let extra = parsed.extra_header_data.as_ref().unwrap();
+        assert_eq!(extra.details.unwrap().unknown_3c, 7);
+        assert_eq!(extra.extra, vec![8; 8]);
+
+        // too short to hold the details, kept as raw bytes
+        hip.extra_header_data = Some(BBCFHipExtra {
+            x_offset: 0,
+            y_offset: 0,
+            details: None,
+            extra: vec![9; 4],
+        });
+        let bytes = hip.to_bytes().unwrap();
+        let parsed = BBCFHip::parse(&bytes).unwrap();
+        assert!(parsed.extra_header_data.as_ref().unwrap().details.is_none());
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    }
+}
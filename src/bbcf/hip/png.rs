@@ -0,0 +1,442 @@
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    helpers::{IndexedImage, RGBAColor},
+    traits::Palette,
+    Error,
+};
+
+use super::{BBCFHip, BBCFHipExtra, BBCFHipFlags, BBCFHipImage, BBCFHipQuirks, LumaPixel};
+
+/// Keyword of the `tEXt` chunk holding the HIP header fields that a PNG can't express
+pub const PNG_METADATA_KEYWORD: &str = "arcsys-hip";
+
+/// Version written when a PNG carries no HIP metadata.
+/// This is what every HIP seen so far uses, but it may not be a version at all
+pub const DEFAULT_VERSION: u32 = 0x125;
+
+/// Header fields that don't survive the trip through plain pixel data
+#[derive(Serialize, Deserialize)]
+struct PngMetadata {
+    version: u32,
+    texture_dimensions: (u32, u32),
+    flags: BBCFHipFlags,
+    extra_header_data: Option<BBCFHipExtra>,
+    #[serde(default)]
+    quirks: BBCFHipQuirks,
+}
+
+/// How luma images are stored in a PNG
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LumaPng {
+    /// 16-bit grayscale holding the values as they are
+    Sixteen,
+    /// 8-bit gray and alpha, split as described by [`LumaPixel`]
+    GrayAlpha,
+}
+
+impl BBCFHip {
+    /// Encode the image as a PNG.
+    ///
+    /// Indexed images are written with a PLTE (and tRNS if any color is translucent),
+    /// raw images as 8-bit RGBA and luma images as 16-bit grayscale, keeping their values as they are.
+    /// If `metadata` is set the rest of the header, including the offsets in
+    /// `extra_header_data`, is stored in a text chunk so [`BBCFHip::from_png`] can restore it
+    pub fn to_png(&self, metadata: bool) -> Result<Vec<u8>, Error> {
+        self.encode_png(metadata, LumaPng::Sixteen)
+    }
+
+    /// Like [`BBCFHip::to_png`], but luma images are written as 8-bit grayscale with alpha.
+    /// This relies on the unchecked layout described by [`LumaPixel`], read the PNG back with
+    /// [`BBCFHip::from_png_gray_alpha`]
+    pub fn to_png_gray_alpha(&self, metadata: bool) -> Result<Vec<u8>, Error> {
+        self.encode_png(metadata, LumaPng::GrayAlpha)
+    }
+
+    fn encode_png(&self, metadata: bool, luma: LumaPng) -> Result<Vec<u8>, Error> {
+        let (width, height) = (self.image.width(), self.image.height());
+
+        let mut bytes = Vec::new();
+        let mut encoder = Encoder::new(&mut bytes, width, height);
+
+        let pixels = match &self.image {
+            BBCFHipImage::Indexed { data, .. } => {
+                if data.palette.is_empty() || data.palette.len() > 256 {
+                    return Err(Error::Rebuilder(format!(
+                        "PNG palettes must have 1 to 256 colors, got {}",
+                        data.palette.len()
+                    )));
+                }
+
+                let rgba = data.get_palette_bytes();
+                let rgb = rgba
+                    .chunks_exact(4)
+                    .flat_map(|c| &c[..3])
+                    .copied()
+                    .collect::<Vec<u8>>();
+                let mut alpha = rgba.chunks_exact(4).map(|c| c[3]).collect::<Vec<u8>>();
+                // trailing opaque entries can be left out of tRNS
+                while alpha.last() == Some(&u8::MAX) {
+                    alpha.pop();
+                }
+
+                encoder.set_color(ColorType::Indexed);
+                encoder.set_depth(BitDepth::Eight);
+                encoder.set_palette(rgb);
+                if !alpha.is_empty() {
+                    encoder.set_trns(alpha);
+                }
+
+                data.image.clone()
+            }
+            BBCFHipImage::Raw { data, .. } => {
+                encoder.set_color(ColorType::Rgba);
+                encoder.set_depth(BitDepth::Eight);
+
+                data.iter().flat_map(|c| c.to_rgba_slice()).collect()
+            }
+            BBCFHipImage::Luma { data, .. } if luma == LumaPng::Sixteen => {
+                encoder.set_color(ColorType::Grayscale);
+                encoder.set_depth(BitDepth::Sixteen);
+
+                data.iter().flat_map(|l| l.to_be_bytes()).collect()
+            }
+            BBCFHipImage::Luma { data, .. } => {
+                encoder.set_color(ColorType::GrayscaleAlpha);
+                encoder.set_depth(BitDepth::Eight);
+
+                data.iter()
+                    .flat_map(|&l| {
+                        let pixel = LumaPixel::from(l);
+                        [pixel.luma, pixel.alpha]
+                    })
+                    .collect()
+            }
+        };
+
+        if metadata {
+            let metadata = PngMetadata {
+                version: self.version,
+                texture_dimensions: self.texture_dimensions,
+                flags: self.flags,
+                extra_header_data: self.extra_header_data.clone(),
+                quirks: self.quirks.clone(),
+            };
+            let text =
+                serde_json::to_string(&metadata).map_err(|e| Error::Rebuilder(e.to_string()))?;
+
+            encoder
+                .add_text_chunk(PNG_METADATA_KEYWORD.to_string(), text)
+                .map_err(|e| Error::Rebuilder(e.to_string()))?;
+        }
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| Error::Rebuilder(e.to_string()))?;
+        writer
+            .write_image_data(&pixels)
+            .map_err(|e| Error::Rebuilder(e.to_string()))?;
+        writer
+            .finish()
+            .map_err(|e| Error::Rebuilder(e.to_string()))?;
+
+        Ok(bytes)
+    }
+
+    /// Decode a PNG into a HIP.
+    ///
+    /// Indexed PNGs become [`BBCFHipImage::Indexed`], 16-bit grayscale becomes
+    /// [`BBCFHipImage::Luma`] and everything else is expanded to [`BBCFHipImage::Raw`].
+    /// Header fields are taken from the metadata text chunk written by [`BBCFHip::to_png`]
+    /// when present, otherwise defaults matching the image size are used
+    pub fn from_png(bytes: &[u8]) -> Result<BBCFHip, Error> {
+        Self::decode_png(bytes, LumaPng::Sixteen)
+    }
+
+    /// Like [`BBCFHip::from_png`], but 8-bit grayscale with alpha also becomes [`BBCFHipImage::Luma`]
+    /// through [`LumaPixel`], undoing [`BBCFHip::to_png_gray_alpha`]
+    pub fn from_png_gray_alpha(bytes: &[u8]) -> Result<BBCFHip, Error> {
+        Self::decode_png(bytes, LumaPng::GrayAlpha)
+    }
+
+    fn decode_png(bytes: &[u8], luma: LumaPng) -> Result<BBCFHip, Error> {
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_transformations(Transformations::IDENTITY);
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| Error::Parser(e.to_string()))?;
+
+        let info = reader.info();
+        let (width, height) = (info.width, info.height);
+        let (color_type, bit_depth) = (info.color_type, info.bit_depth);
+
+        let metadata = info
+            .uncompressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == PNG_METADATA_KEYWORD)
+            .map(|chunk| serde_json::from_str::<PngMetadata>(&chunk.text))
+            .transpose()
+            .map_err(|e| Error::Parser(format!("invalid HIP metadata: {e}")))?;
+
+        let image = match (color_type, bit_depth) {
+            (ColorType::Indexed, _) => {
+                let plte = info
+                    .palette
+                    .as_ref()
+                    .ok_or_else(|| Error::Parser("indexed PNG has no palette".into()))?;
+                let trns = info.trns.as_deref().unwrap_or(&[]);
+
+                let palette = plte
+                    .chunks_exact(3)
+                    .enumerate()
+                    .map(|(i, c)| RGBAColor {
+                        red: c[0],
+                        green: c[1],
+                        blue: c[2],
+                        alpha: trns.get(i).copied().unwrap_or(u8::MAX),
+                    })
+                    .collect();
+
+                let buf = read_frame(&mut reader)?;
+                let image = unpack_indices(&buf, width, height, bit_depth as u8);
+
+                BBCFHipImage::Indexed {
+                    width,
+                    height,
+                    data: IndexedImage { palette, image },
+                }
+            }
+            (ColorType::Grayscale, BitDepth::Sixteen) => {
+                let buf = read_frame(&mut reader)?;
+                let data = buf
+                    .chunks_exact(2)
+                    .map(|l| u16::from_be_bytes([l[0], l[1]]))
+                    .collect();
+
+                BBCFHipImage::Luma {
+                    width,
+                    height,
+                    data,
+                }
+            }
+            (ColorType::GrayscaleAlpha, BitDepth::Eight) if luma == LumaPng::GrayAlpha => {
+                let buf = read_frame(&mut reader)?;
+                let pixels = buf
+                    .chunks_exact(2)
+                    .map(|p| LumaPixel {
+                        luma: p[0],
+                        alpha: p[1],
+                    })
+                    .collect::<Vec<_>>();
+
+                BBCFHipImage::from_luma_pixels(width, height, &pixels)
+            }
+            _ => {
+                // everything else gets normalized to 8-bit channels and widened to RGBA
+                let mut decoder = Decoder::new(bytes);
+                decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+                let mut reader = decoder
+                    .read_info()
+                    .map_err(|e| Error::Parser(e.to_string()))?;
+                let (color_type, _) = reader.output_color_type();
+
+                let buf = read_frame(&mut reader)?;
+                let data = match color_type {
+                    ColorType::Grayscale => buf
+                        .iter()
+                        .map(|&l| RGBAColor {
+                            red: l,
+                            green: l,
+                            blue: l,
+                            alpha: u8::MAX,
+                        })
+                        .collect(),
+                    ColorType::GrayscaleAlpha => buf
+                        .chunks_exact(2)
+                        .map(|c| RGBAColor {
+                            red: c[0],
+                            green: c[0],
+                            blue: c[0],
+                            alpha: c[1],
+                        })
+                        .collect(),
+                    ColorType::Rgb => buf
+                        .chunks_exact(3)
+                        .map(|c| RGBAColor {
+                            red: c[0],
+                            green: c[1],
+                            blue: c[2],
+                            alpha: u8::MAX,
+                        })
+                        .collect(),
+                    ColorType::Rgba => buf
+                        .chunks_exact(4)
+                        .map(|c| RGBAColor {
+                            red: c[0],
+                            green: c[1],
+                            blue: c[2],
+                            alpha: c[3],
+                        })
+                        .collect(),
+                    ColorType::Indexed => unreachable!("indexed PNGs are expanded"),
+                };
+
+                BBCFHipImage::Raw {
+                    width,
+                    height,
+                    data,
+                }
+            }
+        };
+
+        let hip = match metadata {
+            Some(metadata) => BBCFHip {
+                version: metadata.version,
+                texture_dimensions: metadata.texture_dimensions,
+                flags: metadata.flags,
+                extra_header_data: metadata.extra_header_data,
+                image,
+                quirks: metadata.quirks,
+            },
+            None => BBCFHip {
+                version: DEFAULT_VERSION,
+                texture_dimensions: (width, height),
+                flags: BBCFHipFlags::default(),
+                extra_header_data: None,
+                image,
+                quirks: Default::default(),
+            },
+        };
+
+        Ok(hip)
+    }
+}
+
+fn read_frame<R: std::io::Read>(reader: &mut png::Reader<R>) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buf)
+        .map_err(|e| Error::Parser(e.to_string()))?;
+    buf.truncate(frame.buffer_size());
+
+    Ok(buf)
+}
+
+/// Unpacks palette indexes stored at less than 8 bits per pixel,
+/// each row starts on a new byte
+fn unpack_indices(buf: &[u8], width: u32, height: u32, bits: u8) -> Vec<u8> {
+    if bits == 8 {
+        return buf.to_vec();
+    }
+
+    let width = width as usize;
+    let per_byte = 8 / bits as usize;
+    let row_size = width.div_ceil(per_byte);
+    let mask = (1u8 << bits) - 1;
+
+    buf.chunks_exact(row_size)
+        .take(height as usize)
+        .flat_map(|row| {
+            (0..width).map(move |x| {
+                let shift = 8 - bits as usize * (x % per_byte + 1);
+                (row[x / per_byte] >> shift) & mask
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hip_with(image: BBCFHipImage, extra_header_data: Option<BBCFHipExtra>) -> BBCFHip {
+        BBCFHip {
+            version: DEFAULT_VERSION,
+            texture_dimensions: (4, 4),
+            flags: [1, 2, 3].into(),
+            extra_header_data,
+            image,
+            quirks: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_png_roundtrip() {
+        let palette = vec![
+            RGBAColor {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: 0,
+            },
+            RGBAColor {
+                red: 255,
+                green: 10,
+                blue: 20,
+                alpha: 255,
+            },
+        ];
+        let indexed = hip_with(
+            BBCFHipImage::Indexed {
+                width: 3,
+                height: 2,
+                data: IndexedImage {
+                    palette: palette.clone(),
+                    image: vec![0, 1, 1, 1, 0, 0],
+                },
+            },
+            Some(BBCFHipExtra::new(12, 34)),
+        );
+
+        let png = indexed.to_png(true).unwrap();
+        let parsed = BBCFHip::from_png(&png).unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), indexed.to_bytes().unwrap());
+        assert_eq!(parsed.extra_header_data.unwrap().y_offset, 34);
+
+        let without_metadata = BBCFHip::from_png(&indexed.to_png(false).unwrap()).unwrap();
+        assert_eq!(without_metadata.texture_dimensions, (3, 2));
+        assert!(without_metadata.extra_header_data.is_none());
+
+        let raw = hip_with(
+            BBCFHipImage::Raw {
+                width: 2,
+                height: 1,
+                data: palette,
+            },
+            None,
+        );
+        let parsed = BBCFHip::from_png(&raw.to_png(true).unwrap()).unwrap();
+        assert!(matches!(parsed.image, BBCFHipImage::Raw { .. }));
+        assert_eq!(parsed.to_bytes().unwrap(), raw.to_bytes().unwrap());
+
+        let luma = hip_with(
+            BBCFHipImage::Luma {
+                width: 2,
+                height: 2,
+                data: vec![0, 0x1234, 0xFFFF, 0x8000],
+            },
+            None,
+        );
+        let parsed = BBCFHip::from_png(&luma.to_png(true).unwrap()).unwrap();
+        assert!(matches!(parsed.image, BBCFHipImage::Luma { .. }));
+        assert_eq!(parsed.to_bytes().unwrap(), luma.to_bytes().unwrap());
+
+        // gray and alpha only round-trips when asked for on both ends
+        let gray_alpha = luma.to_png_gray_alpha(true).unwrap();
+        let parsed = BBCFHip::from_png_gray_alpha(&gray_alpha).unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), luma.to_bytes().unwrap());
+        let parsed = BBCFHip::from_png(&gray_alpha).unwrap();
+        assert!(matches!(parsed.image, BBCFHipImage::Raw { .. }));
+        // 16-bit grayscale is still read losslessly
+        let parsed = BBCFHip::from_png_gray_alpha(&luma.to_png(false).unwrap()).unwrap();
+        assert_eq!(parsed.image.luma_pixels(), luma.image.luma_pixels());
+    }
+
+    #[test]
+    fn test_unpack_indices() {
+        // 3 pixels per row at 2 bits, rows padded to a byte
+        let packed = [0b00_01_10_00, 0b11_10_01_00];
+        assert_eq!(unpack_indices(&packed, 3, 2, 2), vec![0, 1, 2, 3, 2, 1]);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use crate::{
+    helpers::{IndexedImage, RGBAColor},
+    Error,
+};
+
+use super::{render::color_lookup, BBCFHipImage, TRANSPARENT};
+
+/// How pixels are mapped onto a reduced palette
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dithering {
+    /// Every pixel takes its nearest palette color
+    #[default]
+    None,
+    /// The color error of each pixel is spread onto its unvisited neighbours.
+    /// Alpha is never dithered so sprite edges stay clean
+    FloydSteinberg,
+}
+
+impl IndexedImage {
+    /// Quantize RGBA pixels down to at most `max_colors` (up to 256) colors using median cut.
+    ///
+    /// Index 0 is reserved for transparency, see [`median_cut`],
+    /// and the remaining colors are picked from the visible pixels
+    pub fn quantize(
+        pixels: &[RGBAColor],
+        width: u32,
+        height: u32,
+        max_colors: usize,
+        dithering: Dithering,
+    ) -> Result<IndexedImage, Error> {
+        if !(1..=256).contains(&max_colors) {
+            return Err(Error::Rebuilder(format!(
+                "palette size must be between 1 and 256, got {max_colors}"
+            )));
+        }
+
+        let palette = median_cut(pixels, max_colors);
+
+        IndexedImage::quantize_with_palette(pixels, width, height, &palette, dithering)
+    }
+
+    /// Map RGBA pixels onto an existing palette, e.g. `&hpl.palette` of a character,
+    /// so the result stays usable with every palette sharing that layout.
+    /// Index 0 is drawn transparent whatever the palette stores there, so only transparent pixels use it
+    pub fn quantize_with_palette(
+        pixels: &[RGBAColor],
+        width: u32,
+        height: u32,
+        palette: &[RGBAColor],
+        dithering: Dithering,
+    ) -> Result<IndexedImage, Error> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(Error::Rebuilder(format!(
+                "palette size must be between 1 and 256, got {}",
+                palette.len()
+            )));
+        }
+        let len = width as u64 * height as u64;
+        if pixels.len() as u64 != len {
+            return Err(Error::Rebuilder(format!(
+                "expected {len} pixels for a {width}x{height} image, got {}",
+                pixels.len()
+            )));
+        }
+
+        // match against the colors the indexes are actually drawn with
+        let lookup = color_lookup(palette);
+        let drawn = &lookup[..palette.len()];
+
+        let image = match dithering {
+            Dithering::None => {
+                let mut cache = HashMap::new();
+                pixels
+                    .iter()
+                    .map(|p| {
+                        *cache
+                            .entry(p.to_rgba_slice())
+                            .or_insert_with(|| nearest(drawn, p))
+                    })
+                    .collect()
+            }
+            Dithering::FloydSteinberg => floyd_steinberg(pixels, width as usize, drawn),
+        };
+
+        Ok(IndexedImage {
+            palette: palette.to_vec(),
+            image,
+        })
+    }
+}
+
+impl BBCFHipImage {
+    /// Convert to an indexed image with at most `max_colors` colors
+    pub fn to_indexed(
+        &self,
+        max_colors: usize,
+        dithering: Dithering,
+    ) -> Result<BBCFHipImage, Error> {
+        let pixels = self.rgba_pixels();
+        let data =
+            IndexedImage::quantize(&pixels, self.width(), self.height(), max_colors, dithering)?;
+
+        Ok(BBCFHipImage::Indexed {
+            width: self.width(),
+            height: self.height(),
+            data,
+        })
+    }
+
+    /// Convert to an indexed image using a fixed palette.
+    /// Indexed images are remapped onto the new palette
+    pub fn to_indexed_with_palette(
+        &self,
+        palette: &[RGBAColor],
+        dithering: Dithering,
+    ) -> Result<BBCFHipImage, Error> {
+        let pixels = self.rgba_pixels();
+        let data = IndexedImage::quantize_with_palette(
+            &pixels,
+            self.width(),
+            self.height(),
+            palette,
+            dithering,
+        )?;
+
+        Ok(BBCFHipImage::Indexed {
+            width: self.width(),
+            height: self.height(),
+            data,
+        })
+    }
+}
+
+/// Pick up to `max_colors` representative colors with median cut.
+///
+/// Index 0 is always transparent, since that's how it's drawn whatever its color,
+/// and fully transparent pixels share it
+pub fn median_cut(pixels: &[RGBAColor], max_colors: usize) -> Vec<RGBAColor> {
+    let mut counts: HashMap<[u8; 4], u32> = HashMap::new();
+    for p in pixels.iter().filter(|p| p.alpha != 0) {
+        *counts.entry(p.to_rgba_slice()).or_default() += 1;
+    }
+
+    let mut palette = vec![TRANSPARENT];
+
+    let budget = max_colors.saturating_sub(palette.len());
+    if counts.is_empty() || budget == 0 {
+        return palette;
+    }
+
+    let mut boxes = vec![ColorBox {
+        colors: counts.into_iter().collect(),
+    }];
+
+    while boxes.len() < budget {
+        // split the box spanning the widest channel range
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.colors.len() > 1)
+            .map(|(i, b)| (i, b.widest_channel()))
+            .max_by_key(|&(_, (_, range))| range)
+            .map(|(i, (channel, _))| (i, channel))
+        else {
+            break;
+        };
+
+        let split = boxes[index].split(channel);
+        boxes.push(split);
+    }
+
+    palette.extend(boxes.iter().map(ColorBox::average));
+    palette
+}
+
+struct ColorBox {
+    colors: Vec<([u8; 4], u32)>,
+}
+
+impl ColorBox {
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..4)
+            .map(|c| {
+                let min = self.colors.iter().map(|(p, _)| p[c]).min().unwrap_or(0);
+                let max = self.colors.iter().map(|(p, _)| p[c]).max().unwrap_or(0);
+                (c, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap()
+    }
+
+    /// Split at the pixel-weighted median of `channel`, returning the upper half
+    fn split(&mut self, channel: usize) -> ColorBox {
+        self.colors.sort_unstable_by_key(|(p, _)| p[channel]);
+
+        let total: u32 = self.colors.iter().map(|(_, n)| n).sum();
+        let mut seen = 0;
+        let median = self
+            .colors
+            .iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen * 2 >= total
+            })
+            .unwrap_or(0);
+
+        // both halves need at least one color
+        let at = (median + 1).clamp(1, self.colors.len() - 1);
+
+        ColorBox {
+            colors: self.colors.split_off(at),
+        }
+    }
+
+    fn average(&self) -> RGBAColor {
+        let total: u64 = self.colors.iter().map(|&(_, n)| n as u64).sum();
+        let channel = |c: usize| {
+            let sum: u64 = self
+                .colors
+                .iter()
+                .map(|(p, n)| p[c] as u64 * *n as u64)
+                .sum();
+            ((sum + total / 2) / total) as u8
+        };
+
+        RGBAColor {
+            red: channel(0),
+            green: channel(1),
+            blue: channel(2),
+            alpha: channel(3),
+        }
+    }
+}
+
+fn distance(a: &RGBAColor, b: &RGBAColor) -> u32 {
+    // the color of an invisible pixel doesn't matter
+    if a.alpha == 0 && b.alpha == 0 {
+        return 0;
+    }
+
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.red, b.red) + d(a.green, b.green) + d(a.blue, b.blue) + d(a.alpha, b.alpha)
+}
+
+fn nearest(palette: &[RGBAColor], color: &RGBAColor) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| distance(p, color))
+        .map_or(0, |(i, _)| i as u8)
+}
+
+fn floyd_steinberg(pixels: &[RGBAColor], width: usize, palette: &[RGBAColor]) -> Vec<u8> {
+    let mut errors = vec![[0i32; 3]; pixels.len()];
+    let mut image = Vec::with_capacity(pixels.len());
+
+    for (i, pixel) in pixels.iter().enumerate() {
+        if pixel.alpha == 0 {
+            image.push(nearest(palette, pixel));
+            continue;
+        }
+
+        let e = errors[i];
+        let adjust = |c: u8, e: i32| (c as i32 + e).clamp(0, 255) as u8;
+        let wanted = RGBAColor {
+            red: adjust(pixel.red, e[0]),
+            green: adjust(pixel.green, e[1]),
+            blue: adjust(pixel.blue, e[2]),
+            alpha: pixel.alpha,
+        };
+
+        let index = nearest(palette, &wanted);
+        image.push(index);
+
+        let got = palette[index as usize];
+        let error = [
+            wanted.red as i32 - got.red as i32,
+            wanted.green as i32 - got.green as i32,
+            wanted.blue as i32 - got.blue as i32,
+        ];
+
+        let x = i % width;
+        let mut spread = |target: usize, weight: i32| {
+            if let Some(t) = errors.get_mut(target) {
+                for (t, e) in t.iter_mut().zip(error) {
+                    *t += e * weight / 16;
+                }
+            }
+        };
+
+        if x + 1 < width {
+            spread(i + 1, 7);
+            spread(i + width + 1, 1);
+        }
+        if x > 0 {
+            spread(i + width - 1, 3);
+        }
+        spread(i + width, 5);
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> RGBAColor {
+        RGBAColor {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        let pixels = (0..64)
+            .map(|i| match i % 4 {
+                0 => rgba(0, 0, 0, 0),
+                1 => rgba(250, 0, 0, 255),
+                2 => rgba(255, 5, 0, 255),
+                _ => rgba(0, 0, 200, 255),
+            })
+            .collect::<Vec<_>>();
+
+        let indexed = IndexedImage::quantize(&pixels, 8, 8, 3, Dithering::None).unwrap();
+        assert_eq!(indexed.palette.len(), 3);
+        assert_eq!(indexed.palette[0], TRANSPARENT);
+        assert_eq!(indexed.image[0], 0);
+        assert_eq!(indexed.image[1], indexed.image[2]);
+        assert_ne!(indexed.image[1], indexed.image[3]);
+
+        // a fixed palette is kept as-is, dithered or not
+        let palette = vec![TRANSPARENT, rgba(0, 0, 0, 255), rgba(255, 255, 255, 255)];
+        let gray = vec![rgba(128, 128, 128, 255); 16];
+        let plain =
+            IndexedImage::quantize_with_palette(&gray, 4, 4, &palette, Dithering::None).unwrap();
+        assert_eq!(plain.palette, palette);
+        assert!(plain.image.iter().all(|&i| i == plain.image[0]));
+
+        let dithered =
+            IndexedImage::quantize_with_palette(&gray, 4, 4, &palette, Dithering::FloydSteinberg)
+                .unwrap();
+        assert!(dithered.image.contains(&1) && dithered.image.contains(&2));
+
+        assert!(IndexedImage::quantize(&pixels, 8, 8, 0, Dithering::None).is_err());
+        assert!(IndexedImage::quantize(&pixels, 4, 4, 16, Dithering::None).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{bbcf::hpl::BBCFHpl, helpers::RGBAColor, Error};
+
+use super::{BBCFHip, BBCFHipImage, TRANSPARENT};
+
+impl BBCFHip {
+    /// Render an indexed HIP with one of the character's palettes into a raw RGBA image.
+    ///
+    /// Index 0 is always transparent, whatever color the palette stores there,
+    /// and indexes past the end of a short palette are transparent as well
+    pub fn render_with_palette(&self, hpl: &BBCFHpl) -> Result<BBCFHipImage, Error> {
+        self.image.render_with_palette(&hpl.palette)
+    }
+}
+
+impl BBCFHipImage {
+    /// Render an indexed image with a palette into a raw RGBA image,
+    /// see [`BBCFHip::render_with_palette`]
+    pub fn render_with_palette(&self, palette: &[RGBAColor]) -> Result<BBCFHipImage, Error> {
+        let BBCFHipImage::Indexed {
+            width,
+            height,
+            data,
+        } = self
+        else {
+            return Err(Error::Rebuilder(
+                "only indexed images can be rendered with a palette".into(),
+            ));
+        };
+
+        let lookup = color_lookup(palette);
+
+        Ok(BBCFHipImage::Raw {
+            width: *width,
+            height: *height,
+            data: data.image.iter().map(|&i| lookup[i as usize]).collect(),
+        })
+    }
+}
+
+/// The color each index is drawn with. Index 0 and indexes past the end of the palette are
+/// transparent, every conversion of indexed pixels to colors goes through this
+pub(super) fn color_lookup(palette: &[RGBAColor]) -> [RGBAColor; 256] {
+    let mut lookup = [TRANSPARENT; 256];
+    for (color, entry) in lookup.iter_mut().zip(palette).skip(1) {
+        *color = *entry;
+    }
+
+    lookup
+}
+
+/// A single sprite rendered with a single palette by [`render_all`]
+pub struct Recolor {
+    /// Index into the sprites given to [`render_all`]
+    pub hip: usize,
+    /// Index into the palettes given to [`render_all`]
+    pub palette: usize,
+    pub image: BBCFHipImage,
+}
+
+/// Lazily render every sprite with every palette, sprite by sprite.
+/// Sprites that aren't indexed produce an error for each palette
+pub fn render_all<'a>(
+    hips: &'a [BBCFHip],
+    palettes: &'a [BBCFHpl],
+) -> impl Iterator<Item = Result<Recolor, Error>> + 'a {
+    hips.iter().enumerate().flat_map(move |(hip_index, hip)| {
+        palettes
+            .iter()
+            .enumerate()
+            .map(move |(palette_index, hpl)| {
+                Ok(Recolor {
+                    hip: hip_index,
+                    palette: palette_index,
+                    image: hip.render_with_palette(hpl)?,
+                })
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bbcf::hip::Dithering, helpers::IndexedImage};
+
+    fn gray(value: u8) -> RGBAColor {
+        RGBAColor {
+            red: value,
+            green: value,
+            blue: value,
+            alpha: 255,
+        }
+    }
+
+    #[test]
+    fn test_render_with_palette() {
+        let hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Indexed {
+                width: 3,
+                height: 1,
+                data: IndexedImage {
+                    palette: vec![gray(0); 256],
+                    image: vec![0, 1, 200],
+                },
+            },
+            false,
+        );
+        let hpl = |value| BBCFHpl {
+            version: 0,
+            header: Default::default(),
+            palette: vec![gray(value); 16],
+        };
+        let palettes = [hpl(10), hpl(20)];
+
+        let BBCFHipImage::Raw { data, .. } = hip.render_with_palette(&palettes[0]).unwrap() else {
+            panic!("expected a raw image");
+        };
+        assert_eq!(data, vec![TRANSPARENT, gray(10), TRANSPARENT]);
+
+        let renders = render_all(std::slice::from_ref(&hip), &palettes)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(renders.len(), 2);
+        assert_eq!(renders[1].palette, 1);
+
+        let raw = BBCFHip::from_full_canvas(renders[1].image.clone(), false);
+        assert!(raw.render_with_palette(&palettes[0]).is_err());
+
+        // an opaque index 0 is transparent whichever way the sprite is drawn
+        let image = BBCFHipImage::Indexed {
+            width: 3,
+            height: 1,
+            data: IndexedImage {
+                palette: vec![gray(10), gray(20)],
+                image: vec![0, 1, 0],
+            },
+        };
+        let BBCFHipImage::Raw { data, .. } = image.to_raw() else {
+            panic!("expected a raw image");
+        };
+        assert_eq!(data, vec![TRANSPARENT, gray(20), TRANSPARENT]);
+        assert_eq!(image.content_bounds(), Some((1, 0, 1, 1)));
+
+        let hip = BBCFHip::from_full_canvas(image, true);
+        assert_eq!(hip.image.width(), 1);
+
+        let palette = [gray(10), gray(20)];
+        let indexed = hip.image.to_indexed_with_palette(&palette, Dithering::None);
+        let BBCFHipImage::Indexed { data, .. } = indexed.unwrap() else {
+            panic!("expected an indexed image");
+        };
+        assert_eq!(data.image, vec![1], "visible pixels never map to index 0");
+    }
+}
@@ -0,0 +1,383 @@
+//! The run-length encoding shared by every HIP image type.
+//!
+//! Image data is a list of `(value, count)` pairs, where the value is a single pixel
+//! and the count is a `u8`, so longer runs are split into several pairs
+
+use serde::{Deserialize, Serialize};
+
+use crate::{helpers::RGBAColor, Error};
+
+/// Where an encoder breaks up runs of identical pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RunSplit {
+    /// Runs only break when the value changes or the count is full
+    #[default]
+    Continuous,
+    /// Runs additionally break at the end of every row.
+    /// Speculative, no game file has been confirmed to use this, it's only detected when the runs fit it
+    Rows,
+}
+
+/// A pixel value that can be stored in a HIP run
+pub trait RunValue: Copy + PartialEq {
+    /// Size of the value in bytes, not counting the run length
+    const SIZE: usize;
+
+    /// Read the value from exactly [`RunValue::SIZE`] bytes
+    fn from_bytes(bytes: &[u8]) -> Self;
+
+    /// Append the encoded value to `out`
+    fn write_bytes(&self, out: &mut Vec<u8>);
+}
+
+/// Palette indexes of indexed images
+impl RunValue for u8 {
+    const SIZE: usize = 1;
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+
+    #[inline]
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+/// Little endian luma values
+impl RunValue for u16 {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Self {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    }
+
+    #[inline]
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+/// Raw colors, stored as ARGB
+impl RunValue for RGBAColor {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Self {
+        RGBAColor {
+            red: bytes[1],
+            green: bytes[2],
+            blue: bytes[3],
+            alpha: bytes[0],
+        }
+    }
+
+    #[inline]
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_argb_slice());
+    }
+}
+
+/// Decode exactly `len` pixels from the start of `input`.
+///
+/// Returns the pixels along with the amount of bytes consumed,
+/// anything after the last run is left untouched
+pub fn decode_runs<T: RunValue>(input: &[u8], len: usize) -> Result<(Vec<T>, usize), Error> {
+    let stride = T::SIZE + 1;
+
+    // a corrupt size can't make this allocate more than the runs could ever hold
+    let mut pixels = Vec::with_capacity(len.min(input.len() / stride * u8::MAX as usize));
+    let mut pos = 0;
+
+    while pixels.len() < len {
+        let Some(run) = input.get(pos..pos + stride) else {
+            return Err(Error::Parser(format!(
+                "run-length data ends after {} of {len} pixels",
+                pixels.len()
+            )));
+        };
+
+        let value = T::from_bytes(&run[..T::SIZE]);
+        let count = run[T::SIZE] as usize;
+
+        if pixels.len() + count > len {
+            return Err(Error::Parser(format!(
+                "run-length data overflows image by {} pixels",
+                pixels.len() + count - len
+            )));
+        }
+
+        pixels.resize(pixels.len() + count, value);
+        pos += stride;
+    }
+
+    Ok((pixels, pos))
+}
+
+/// Lazily decodes the pixels of run-length data, one at a time.
+///
+/// Only the runs that are actually reached get read, so iteration can stop early
+/// and [`RunDecoder::skip`] jumps ahead by whole runs without expanding them.
+/// Iteration ends after the first error
+pub struct RunDecoder<'a, T> {
+    input: &'a [u8],
+    pos: usize,
+    /// Value of the current run and how many of its pixels are left
+    run: Option<(T, usize)>,
+    /// Pixels left in the image, including the rest of the current run
+    remaining: usize,
+}
+
+impl<'a, T: RunValue> RunDecoder<'a, T> {
+    /// Decode `len` pixels from the start of `input`
+    pub fn new(input: &'a [u8], len: usize) -> Self {
+        Self {
+            input,
+            pos: 0,
+            run: None,
+            remaining: len,
+        }
+    }
+
+    /// Pixels left to decode
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Bytes of `input` consumed so far
+    pub fn consumed(&self) -> usize {
+        self.pos
+    }
+
+    /// Skip the next `count` pixels, or all of them if there are fewer left
+    pub fn skip(&mut self, mut count: usize) -> Result<(), Error> {
+        count = count.min(self.remaining);
+
+        while count > 0 {
+            let (value, left) = match self.run {
+                Some(run) if run.1 > 0 => run,
+                _ => self.next_run()?,
+            };
+
+            let skipped = left.min(count);
+            self.run = Some((value, left - skipped));
+            self.remaining -= skipped;
+            count -= skipped;
+        }
+
+        Ok(())
+    }
+
+    fn next_run(&mut self) -> Result<(T, usize), Error> {
+        let Some(run) = self.input.get(self.pos..self.pos + T::SIZE + 1) else {
+            self.remaining = 0;
+            return Err(Error::Parser(format!(
+                "run-length data ends after {} bytes",
+                self.pos
+            )));
+        };
+
+        let value = T::from_bytes(&run[..T::SIZE]);
+        let count = run[T::SIZE] as usize;
+
+        if count > self.remaining {
+            let overflow = count - self.remaining;
+            self.remaining = 0;
+            return Err(Error::Parser(format!(
+                "run-length data overflows image by {overflow} pixels"
+            )));
+        }
+
+        self.pos += T::SIZE + 1;
+        Ok((value, count))
+    }
+}
+
+impl<T: RunValue> Iterator for RunDecoder<'_, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let (value, left) = match self.run {
+            Some(run) if run.1 > 0 => run,
+            _ => match self.next_run() {
+                Ok(run) => run,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+
+        self.run = Some((value, left - 1));
+        self.remaining -= 1;
+        Some(Ok(value))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if let Err(e) = self.skip(n) {
+            return Some(Err(e));
+        }
+
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// Encode `pixels` onto the end of `out`
+pub fn encode_runs<T: RunValue>(pixels: &[T], out: &mut Vec<u8>) {
+    // sprites are mostly long runs, so this is usually enough to avoid regrowing
+    out.reserve(pixels.len() / 4);
+
+    let mut rest = pixels;
+    while let Some(&value) = rest.first() {
+        let window = &rest[..rest.len().min(u8::MAX as usize)];
+        let run = window
+            .iter()
+            .position(|p| *p != value)
+            .unwrap_or(window.len());
+
+        value.write_bytes(out);
+        out.push(run as u8);
+        rest = &rest[run..];
+    }
+}
+
+/// Encode `pixels` onto the end of `out`, breaking runs as described by `split`
+pub fn encode_runs_split<T: RunValue>(
+    pixels: &[T],
+    width: usize,
+    split: RunSplit,
+    out: &mut Vec<u8>,
+) {
+    match split {
+        RunSplit::Continuous => encode_runs(pixels, out),
+        RunSplit::Rows => pixels
+            .chunks(width.max(1))
+            .for_each(|row| encode_runs(row, out)),
+    }
+}
+
+/// Find the [`RunSplit`] that reproduces the runs at the start of `input` exactly,
+/// or `None` if the runs were split in some other way
+pub fn detect_run_split<T: RunValue>(input: &[u8], width: usize, len: usize) -> Option<RunSplit> {
+    let (mut continuous, mut rows) = (true, true);
+
+    let mut pos = 0;
+    let mut prev: Option<(&[u8], usize)> = None;
+
+    for run in input.chunks_exact(T::SIZE + 1) {
+        if pos >= len {
+            break;
+        }
+
+        let (value, count) = (&run[..T::SIZE], run[T::SIZE] as usize);
+        if count == 0 {
+            return None;
+        }
+
+        // a run that could have been merged into the previous one
+        if let Some((prev_value, prev_count)) = prev {
+            if prev_value == value && prev_count < u8::MAX as usize {
+                continuous = false;
+                rows &= width > 0 && pos % width == 0;
+            }
+        }
+
+        if width > 0 && pos / width != (pos + count - 1) / width {
+            rows = false;
+        }
+
+        pos += count;
+        prev = Some((value, count));
+    }
+
+    if pos != len {
+        None
+    } else if continuous {
+        Some(RunSplit::Continuous)
+    } else if rows {
+        Some(RunSplit::Rows)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs() {
+        let mut pixels = vec![7u16; 300];
+        pixels.extend([1, 2, 2]);
+
+        let mut encoded = Vec::new();
+        encode_runs(&pixels, &mut encoded);
+        assert_eq!(
+            encoded,
+            [7, 0, 255, 7, 0, 45, 1, 0, 1, 2, 0, 2].to_vec(),
+            "runs are split at 255 pixels"
+        );
+
+        // trailing bytes are not consumed
+        encoded.extend([0, 0]);
+        let (decoded, consumed) = decode_runs::<u16>(&encoded, pixels.len()).unwrap();
+        assert_eq!(decoded, pixels);
+        assert_eq!(consumed, encoded.len() - 2);
+
+        assert!(decode_runs::<u16>(&encoded[..6], pixels.len()).is_err());
+        assert!(decode_runs::<u16>(&encoded, 299).is_err());
+    }
+
+    #[test]
+    fn test_run_decoder() {
+        let mut pixels = vec![3u8; 300];
+        pixels.extend([1, 2, 2]);
+
+        let mut encoded = Vec::new();
+        encode_runs(&pixels, &mut encoded);
+
+        let decoded = RunDecoder::<u8>::new(&encoded, pixels.len())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, pixels);
+
+        // skipping only reads the runs it passes over
+        let mut decoder = RunDecoder::<u8>::new(&encoded, pixels.len());
+        assert_eq!(decoder.nth(299).unwrap().unwrap(), 3);
+        assert_eq!(decoder.consumed(), 4);
+        assert_eq!(decoder.next().unwrap().unwrap(), 1);
+        assert_eq!(decoder.remaining(), 2);
+
+        let mut truncated = RunDecoder::<u8>::new(&encoded[..4], pixels.len());
+        assert!(truncated.nth(300).unwrap().is_err());
+        assert!(truncated.next().is_none());
+    }
+
+    #[test]
+    fn test_run_split() {
+        // two rows of 3 identical pixels
+        let pixels = [5u8; 6];
+
+        let mut continuous = Vec::new();
+        encode_runs_split(&pixels, 3, RunSplit::Continuous, &mut continuous);
+        assert_eq!(continuous, [5, 6]);
+        assert_eq!(
+            detect_run_split::<u8>(&continuous, 3, 6),
+            Some(RunSplit::Continuous)
+        );
+
+        let mut rows = Vec::new();
+        encode_runs_split(&pixels, 3, RunSplit::Rows, &mut rows);
+        assert_eq!(rows, [5, 3, 5, 3]);
+        assert_eq!(detect_run_split::<u8>(&rows, 3, 6), Some(RunSplit::Rows));
+
+        assert_eq!(detect_run_split::<u8>(&[5, 2, 5, 4], 3, 6), None);
+    }
+}
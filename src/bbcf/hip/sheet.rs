@@ -0,0 +1,97 @@
+use crate::{
+    helpers::RGBAColor,
+    pac::{Pac, PacEntry},
+    Error,
+};
+
+use super::{BBCFHip, BBCFHipImage};
+
+/// Every HIP in a PAC along with its [`label`], skipping entries that aren't sprites
+pub(super) fn pac_sprites(
+    pac: &Pac,
+) -> impl Iterator<Item = Result<(String, BBCFHip), Error>> + '_ {
+    pac.entries
+        .iter()
+        .filter(|e| e.contents.starts_with(b"HIP\0"))
+        .map(|entry| Ok((label(entry), BBCFHip::parse(&entry.contents)?)))
+}
+
+/// The entry's name, or its hash if it has none
+fn label(entry: &PacEntry) -> String {
+    entry
+        .name()
+        .map_or_else(|| format!("0x{:0>8X}", entry.hash_id()), str::to_string)
+}
+
+/// How [`Sheet::draw`] combines new pixels with the ones already there
+#[derive(Clone, Copy)]
+pub(super) enum DrawMode {
+    /// Overwrite the sheet, alpha included
+    Copy,
+    /// Blend over the sheet by alpha, keeping the sheet's alpha
+    Blend,
+}
+
+/// An RGBA image that sprites are drawn onto
+pub(super) struct Sheet {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<RGBAColor>,
+}
+
+impl Sheet {
+    pub fn new(width: u32, height: u32, fill: RGBAColor) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width as usize * height as usize],
+        }
+    }
+
+    /// Draw `pixels`, rows of `width`, with their top left corner at `(x, y)`.
+    /// Anything past the edges of the sheet is clipped
+    pub fn draw(&mut self, pixels: &[RGBAColor], width: u32, x: u32, y: u32, mode: DrawMode) {
+        if width == 0 || x >= self.width {
+            return;
+        }
+
+        let visible = width.min(self.width - x) as usize;
+        let rows = pixels
+            .chunks_exact(width as usize)
+            .take(self.height.saturating_sub(y) as usize);
+
+        for (row, line) in rows.enumerate() {
+            let start = (y as usize + row) * self.width as usize + x as usize;
+            let dst = &mut self.pixels[start..start + visible];
+
+            match mode {
+                DrawMode::Copy => dst.copy_from_slice(&line[..visible]),
+                DrawMode::Blend => {
+                    for (dst, src) in dst.iter_mut().zip(line) {
+                        *dst = blend(*src, *dst);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn into_image(self) -> BBCFHipImage {
+        BBCFHipImage::Raw {
+            width: self.width,
+            height: self.height,
+            data: self.pixels,
+        }
+    }
+}
+
+fn blend(src: RGBAColor, dst: RGBAColor) -> RGBAColor {
+    let a = src.alpha as u32;
+    let mix = |s: u8, d: u8| ((s as u32 * a + d as u32 * (255 - a)) / 255) as u8;
+
+    RGBAColor {
+        red: mix(src.red, dst.red),
+        green: mix(src.green, dst.green),
+        blue: mix(src.blue, dst.blue),
+        alpha: dst.alpha,
+    }
+}
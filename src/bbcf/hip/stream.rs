@@ -0,0 +1,225 @@
+use std::io::Cursor;
+
+use crate::{helpers::RGBAColor, Error};
+
+use super::{
+    rle::RunDecoder, BBCFHipExtra, HipHead, HipPixel, ENCODING_ARGB, ENCODING_INDEXED,
+    ENCODING_LUMA,
+};
+
+/// A HIP whose header has been read but whose image data is only decoded on demand,
+/// for previews or scans that don't need the whole image in memory
+pub struct HipStream<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub texture_dimensions: (u32, u32),
+    pub extra_header_data: Option<BBCFHipExtra>,
+    /// The palette of indexed images, empty for other image types
+    pub palette: Vec<RGBAColor>,
+    encoding: u8,
+    data: &'a [u8],
+}
+
+impl<'a> HipStream<'a> {
+    /// Read the header of a HIP, borrowing the image data from `input`
+    pub fn new(input: &'a [u8]) -> Result<Self, Error> {
+        let mut cursor = Cursor::new(input);
+        let head = HipHead::read(&mut cursor).map_err(|e| Error::Parser(e.to_string()))?;
+
+        let encoding = head.header.encoding;
+        if ![ENCODING_INDEXED, ENCODING_ARGB, ENCODING_LUMA].contains(&encoding) {
+            return Err(Error::Parser(format!("unknown HIP encoding {encoding:#x}")));
+        }
+
+        let data_start = cursor.position() as usize;
+        let data_end = (head.header.file_size as usize)
+            .min(input.len())
+            .max(data_start);
+
+        Ok(Self {
+            width: head.width,
+            height: head.height,
+            texture_dimensions: (head.header.texture_width, head.header.texture_height),
+            extra_header_data: head.extra_header_data,
+            palette: head.palette,
+            encoding,
+            data: &input[data_start..data_end],
+        })
+    }
+
+    /// Iterate over every pixel, row by row
+    pub fn pixels(&self) -> HipPixels<'a> {
+        let len = self.width as usize * self.height as usize;
+
+        let decoder = match self.encoding {
+            ENCODING_INDEXED => Decoder::Indexed(RunDecoder::new(self.data, len)),
+            ENCODING_ARGB => Decoder::Raw(RunDecoder::new(self.data, len)),
+            _ => Decoder::Luma(RunDecoder::new(self.data, len)),
+        };
+
+        HipPixels {
+            decoder,
+            width: self.width as usize,
+            len,
+        }
+    }
+
+    /// Iterate over every row, decoding each one as it's reached
+    pub fn rows(&self) -> HipRows<'a> {
+        HipRows {
+            pixels: self.pixels(),
+        }
+    }
+}
+
+enum Decoder<'a> {
+    Indexed(RunDecoder<'a, u8>),
+    Raw(RunDecoder<'a, RGBAColor>),
+    Luma(RunDecoder<'a, u16>),
+}
+
+/// Pixels of a [`HipStream`], see [`HipStream::pixels`]
+pub struct HipPixels<'a> {
+    decoder: Decoder<'a>,
+    width: usize,
+    len: usize,
+}
+
+impl HipPixels<'_> {
+    /// Position `(x, y)` of the next pixel
+    pub fn coordinates(&self) -> (u32, u32) {
+        let index = self.len - self.remaining();
+        match self.width {
+            0 => (0, 0),
+            width => ((index % width) as u32, (index / width) as u32),
+        }
+    }
+
+    /// Skip ahead to the start of `row`, which has to be at or after the current position
+    pub fn skip_to_row(&mut self, row: u32) -> Result<(), Error> {
+        let target = row as usize * self.width;
+        let index = self.len - self.remaining();
+        if target < index {
+            return Err(Error::Parser(format!(
+                "can't go back to row {row} from row {}",
+                self.coordinates().1
+            )));
+        }
+
+        match &mut self.decoder {
+            Decoder::Indexed(d) => d.skip(target - index),
+            Decoder::Raw(d) => d.skip(target - index),
+            Decoder::Luma(d) => d.skip(target - index),
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        match &self.decoder {
+            Decoder::Indexed(d) => d.remaining(),
+            Decoder::Raw(d) => d.remaining(),
+            Decoder::Luma(d) => d.remaining(),
+        }
+    }
+}
+
+impl Iterator for HipPixels<'_> {
+    type Item = Result<HipPixel, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.decoder {
+            Decoder::Indexed(d) => d.next().map(|p| p.map(HipPixel::Index)),
+            Decoder::Raw(d) => d.next().map(|p| p.map(HipPixel::Color)),
+            Decoder::Luma(d) => d.next().map(|p| p.map(HipPixel::Luma)),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match &mut self.decoder {
+            Decoder::Indexed(d) => d.nth(n).map(|p| p.map(HipPixel::Index)),
+            Decoder::Raw(d) => d.nth(n).map(|p| p.map(HipPixel::Color)),
+            Decoder::Luma(d) => d.nth(n).map(|p| p.map(HipPixel::Luma)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining()))
+    }
+}
+
+/// Rows of a [`HipStream`], see [`HipStream::rows`]
+pub struct HipRows<'a> {
+    pixels: HipPixels<'a>,
+}
+
+impl HipRows<'_> {
+    /// Skip ahead so the next row returned is `row`
+    pub fn skip_to_row(&mut self, row: u32) -> Result<(), Error> {
+        self.pixels.skip_to_row(row)
+    }
+}
+
+impl Iterator for HipRows<'_> {
+    type Item = Result<Vec<HipPixel>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let width = self.pixels.width;
+        if width == 0 || self.pixels.remaining() == 0 {
+            return None;
+        }
+
+        Some(self.pixels.by_ref().take(width).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bbcf::hip::{BBCFHip, BBCFHipImage},
+        helpers::IndexedImage,
+    };
+
+    #[test]
+    fn test_hip_stream() {
+        // 3x4 image, each row filled with its row number, row 0 gets trimmed away
+        let image = (0..4u8).flat_map(|row| [row; 3]).collect();
+        let hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Indexed {
+                width: 3,
+                height: 4,
+                data: IndexedImage {
+                    palette: (0..4)
+                        .map(|i| RGBAColor {
+                            red: i,
+                            green: 0,
+                            blue: 0,
+                            alpha: 255,
+                        })
+                        .collect(),
+                    image,
+                },
+            },
+            true,
+        );
+        let bytes = hip.to_bytes().unwrap();
+
+        let stream = HipStream::new(&bytes).unwrap();
+        assert_eq!((stream.width, stream.height), (3, 3));
+        assert_eq!(stream.palette.len(), 4);
+
+        let mut rows = stream.rows();
+        rows.skip_to_row(2).unwrap();
+        assert_eq!(rows.next().unwrap().unwrap(), vec![HipPixel::Index(3); 3]);
+        assert!(rows.next().is_none());
+        assert!(rows.skip_to_row(0).is_err());
+
+        let mut pixels = stream.pixels();
+        assert_eq!(pixels.nth(4).unwrap().unwrap(), HipPixel::Index(2));
+        assert_eq!(pixels.coordinates(), (2, 1));
+
+        assert!(HipStream::new(&bytes[..bytes.len() - 2])
+            .unwrap()
+            .pixels()
+            .any(|p| p.is_err()));
+    }
+}
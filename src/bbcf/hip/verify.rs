@@ -0,0 +1,230 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+use super::BBCFHip;
+
+/// Result of [`verify_roundtrip`]
+#[derive(Debug, Default)]
+pub struct RoundtripReport {
+    /// Amount of HIP files checked
+    pub checked: usize,
+    pub failures: Vec<RoundtripFailure>,
+    /// How many of the parsed files hold each value of the header fields that aren't decoded yet,
+    /// by field name, e.g. `"unknown_19"`
+    pub header_values: BTreeMap<&'static str, BTreeMap<u32, usize>>,
+}
+
+impl RoundtripReport {
+    /// Whether every file rebuilt byte-for-byte
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A HIP file that didn't survive a parse and rebuild unchanged
+#[derive(Debug)]
+pub struct RoundtripFailure {
+    pub path: PathBuf,
+    pub kind: RoundtripFailureKind,
+}
+
+#[derive(Debug)]
+pub enum RoundtripFailureKind {
+    /// The file or its folder couldn't be read
+    Io(String),
+    Parse(String),
+    Rebuild(String),
+    /// The rebuilt file differs, starting at `offset`
+    Mismatch {
+        offset: usize,
+        original_size: usize,
+        rebuilt_size: usize,
+    },
+}
+
+/// Parse and rebuild every `.hip` file in `dir` and its subfolders, not following symlinked folders,
+/// reporting the files that don't come back byte-for-byte.
+/// Only fails if `dir` itself can't be read, any other error is reported for the file it happened on
+pub fn verify_roundtrip(dir: impl AsRef<Path>) -> Result<RoundtripReport, Error> {
+    let mut report = RoundtripReport::default();
+    let mut dirs = vec![dir.as_ref().to_path_buf()];
+    let mut root = true;
+
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect());
+        let mut entries: Vec<PathBuf> = match entries {
+            Ok(entries) => entries,
+            Err(e) if root => return Err(e.into()),
+            Err(e) => {
+                report.failures.push(RoundtripFailure {
+                    path: dir,
+                    kind: RoundtripFailureKind::Io(e.to_string()),
+                });
+                continue;
+            }
+        };
+        entries.sort();
+        root = false;
+
+        for path in entries {
+            if std::fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir()) {
+                dirs.push(path);
+                continue;
+            }
+
+            // symlinked folders aren't followed, one pointing back up the tree would never end
+            if path.is_dir() {
+                continue;
+            }
+
+            let is_hip = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("hip"));
+            if !is_hip {
+                continue;
+            }
+
+            report.checked += 1;
+
+            let kind = match std::fs::read(&path) {
+                Ok(original) => check(&original, &mut report.header_values),
+                Err(e) => Some(RoundtripFailureKind::Io(e.to_string())),
+            };
+            if let Some(kind) = kind {
+                report.failures.push(RoundtripFailure { path, kind });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn check(
+    original: &[u8],
+    header_values: &mut BTreeMap<&'static str, BTreeMap<u32, usize>>,
+) -> Option<RoundtripFailureKind> {
+    let hip = match BBCFHip::parse(original) {
+        Ok(hip) => hip,
+        Err(e) => return Some(RoundtripFailureKind::Parse(e.to_string())),
+    };
+
+    for (field, value) in undecoded_fields(&hip) {
+        *header_values
+            .entry(field)
+            .or_default()
+            .entry(value)
+            .or_default() += 1;
+    }
+
+    let rebuilt = match hip.to_bytes() {
+        Ok(rebuilt) => rebuilt,
+        Err(e) => return Some(RoundtripFailureKind::Rebuild(e.to_string())),
+    };
+
+    if rebuilt == original {
+        return None;
+    }
+
+    let offset = original
+        .iter()
+        .zip(&rebuilt)
+        .position(|(a, b)| a != b)
+        .unwrap_or(original.len().min(rebuilt.len()));
+
+    Some(RoundtripFailureKind::Mismatch {
+        offset,
+        original_size: original.len(),
+        rebuilt_size: rebuilt.len(),
+    })
+}
+
+/// The header fields whose meaning is still guessed at, see [`RoundtripReport::header_values`]
+fn undecoded_fields(hip: &BBCFHip) -> Vec<(&'static str, u32)> {
+    let mut fields = vec![
+        ("version", hip.version),
+        ("unknown_19", hip.flags.unknown_19 as u32),
+        ("unknown_1a", hip.flags.unknown_1a as u32),
+        ("unknown_1b", hip.flags.unknown_1b as u32),
+    ];
+
+    if let Some(details) = hip.extra_header_data.as_ref().and_then(|e| e.details) {
+        fields.extend([
+            ("unknown_30", details.unknown_30),
+            ("unknown_34", details.unknown_34),
+            ("unknown_38", details.unknown_38),
+            ("unknown_3c", details.unknown_3c),
+        ]);
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbcf::hip::{BBCFHipImage, DEFAULT_VERSION};
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("arcsys_verify_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+
+        let hip = BBCFHip::from_full_canvas(
+            BBCFHipImage::Luma {
+                width: 2,
+                height: 1,
+                data: vec![1, 2],
+            },
+            false,
+        );
+        std::fs::write(dir.join("good.hip"), hip.to_bytes().unwrap()).unwrap();
+        std::fs::write(dir.join("nested/bad.HIP"), b"HIP\0").unwrap();
+        std::fs::write(dir.join("ignored.txt"), b"").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken.hip")).unwrap();
+            std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+        }
+
+        let report = verify_roundtrip(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let report = report.unwrap();
+
+        // every file is checked even after one fails
+        let failures: Vec<_> = report
+            .failures
+            .iter()
+            .map(|f| f.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        if cfg!(unix) {
+            assert_eq!(report.checked, 3);
+            assert_eq!(failures, vec!["broken.hip", "bad.HIP"]);
+            assert!(matches!(
+                report.failures[0].kind,
+                RoundtripFailureKind::Io(_)
+            ));
+        } else {
+            assert_eq!(report.checked, 2);
+            assert_eq!(failures, vec!["bad.HIP"]);
+        }
+        assert!(matches!(
+            report.failures.last().unwrap().kind,
+            RoundtripFailureKind::Parse(_)
+        ));
+
+        // only the file that parsed is counted, and it has no extra header
+        assert_eq!(
+            report.header_values["version"],
+            BTreeMap::from([(DEFAULT_VERSION, 1)])
+        );
+        assert_eq!(report.header_values["unknown_1b"], BTreeMap::from([(0, 1)]));
+        assert!(!report.header_values.contains_key("unknown_30"));
+
+        assert!(verify_roundtrip(dir.join("missing")).is_err());
+    }
+}
@@ -10,15 +10,57 @@ use serde::{Deserialize, Serialize};
 
 static MAGIC_HPL: &[u8] = b"HPAL";
 
-#[derive(Serialize, Deserialize)]
+// BBCF palette file structure
+// 00: magic "HPAL"
+// 04: version, see `DEFAULT_VERSION`
+// 08: total file size, see `BBCFHpl::file_size`
+// 0C: amount of colors, see `BBCFHpl::color_count`
+// 10: unknown header fields, see `BBCFHplHeader`
+// 1C: 4-byte padding
+// 20..N: palette of BGRA8 colors
+const HEADER_SIZE: usize = 0x20;
+
+/// Version of the game's palette files, the same one HIPs use
+pub const DEFAULT_VERSION: u32 = crate::bbcf::hip::DEFAULT_VERSION;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BBCFHpl {
+    /// [`DEFAULT_VERSION`] in the game's files, kept as parsed
     pub version: u32,
-    pub unknown_data: (u32, u32, u16, u16),
+    pub header: BBCFHplHeader,
     pub palette: Vec<RGBAColor>,
 }
 
+/// The 12 bytes at 0x10, between the amount of colors and the padding.
+/// The [`Default`] is what the game's palette files hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BBCFHplHeader {
+    /// Unknown, zero in the game's files
+    pub unknown_10: u32,
+    /// Unknown, zero in the game's files
+    pub unknown_14: u32,
+    /// Unknown, 0x10 in the game's files
+    pub unknown_18: u16,
+    /// Unknown, zero in the game's files
+    pub unknown_1a: u16,
+}
+
+impl Default for BBCFHplHeader {
+    fn default() -> Self {
+        Self {
+            unknown_10: 0,
+            unknown_14: 0,
+            unknown_18: 0x10,
+            unknown_1a: 0,
+        }
+    }
+}
+
 impl BBCFHpl {
+    /// Parse a palette, checking the declared file size and palette size against the data
     pub fn parse(input: &[u8]) -> Result<BBCFHpl, Error> {
+        validate_sizes(input)?;
+
         let res = parse_hpl_impl(input);
 
         match res {
@@ -29,6 +71,49 @@ impl BBCFHpl {
             Err(e) => Err(Error::Parser(e.map_input(|_| &[0; 0]).to_string())),
         }
     }
+
+    pub fn builder(palette: Vec<RGBAColor>) -> BBCFHplBuilder {
+        BBCFHplBuilder::new(palette)
+    }
+
+    /// Total size of the file, as written at 0x08
+    pub fn file_size(&self) -> u32 {
+        (HEADER_SIZE + self.palette.len() * 4) as u32
+    }
+
+    /// Amount of colors in the palette, as written at 0x0C
+    pub fn color_count(&self) -> u32 {
+        self.palette.len() as u32
+    }
+}
+
+fn validate_sizes(input: &[u8]) -> Result<(), Error> {
+    if input.len() < HEADER_SIZE {
+        return Err(Error::Parser(format!(
+            "HPL is {} bytes, shorter than its {HEADER_SIZE:#x} byte header",
+            input.len()
+        )));
+    }
+
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(input[offset..offset + 4].try_into().unwrap()) as usize;
+    let (file_size, palette_size) = (read_u32(0x8), read_u32(0xC));
+
+    if file_size != input.len() {
+        return Err(Error::Parser(format!(
+            "HPL declares a size of {file_size:#x} bytes but is {:#x} bytes",
+            input.len()
+        )));
+    }
+
+    let colors = (input.len() - HEADER_SIZE) / 4;
+    if HEADER_SIZE + palette_size * 4 != input.len() {
+        return Err(Error::Parser(format!(
+            "HPL declares {palette_size} colors but holds {colors}"
+        )));
+    }
+
+    Ok(())
 }
 
 fn parse_hpl_impl(i: &[u8]) -> IResult<&[u8], BBCFHpl> {
@@ -38,12 +123,17 @@ fn parse_hpl_impl(i: &[u8]) -> IResult<&[u8], BBCFHpl> {
     let (i, _file_size) = le_u32(i)?;
     let (i, palette_size) = le_u32(i)?;
 
-    let (i, unknown_1) = le_u32(i)?;
-    let (i, unknown_2) = le_u32(i)?;
-    let (i, unknown_3) = le_u16(i)?;
-    let (i, unknown_4) = le_u16(i)?;
+    let (i, unknown_10) = le_u32(i)?;
+    let (i, unknown_14) = le_u32(i)?;
+    let (i, unknown_18) = le_u16(i)?;
+    let (i, unknown_1a) = le_u16(i)?;
 
-    let unknown = (unknown_1, unknown_2, unknown_3, unknown_4);
+    let header = BBCFHplHeader {
+        unknown_10,
+        unknown_14,
+        unknown_18,
+        unknown_1a,
+    };
 
     let (i, _padding) = le_u32(i)?;
 
@@ -51,7 +141,7 @@ fn parse_hpl_impl(i: &[u8]) -> IResult<&[u8], BBCFHpl> {
 
     let hpl = BBCFHpl {
         version,
-        unknown_data: unknown,
+        header,
         palette,
     };
 
@@ -67,33 +157,17 @@ impl BBCFHpl {
         use byteorder::{WriteBytesExt, LE};
         use std::io::Write;
 
-        // BBCF palette file structure
-        // 00: magic "HPAL"
-        // 04: version?
-        // 08: total file size
-        // 0C: palette size
-        // 10: unknown u32, u32, u16, u16
-        // 1C: 4-byte padding
-        // 20..N: palette of BGRA8 colors
-
-        const HEADER_SIZE: usize = 0x20;
-
         let mut final_bytes = Vec::new();
 
         final_bytes.write_all(MAGIC_HPL).unwrap();
         final_bytes.write_u32::<LE>(self.version).unwrap();
-        final_bytes
-            .write_u32::<LE>((HEADER_SIZE + (self.palette.len() * 0x4)) as u32)
-            .unwrap();
-        final_bytes
-            .write_u32::<LE>(self.palette.len() as u32)
-            .unwrap();
+        final_bytes.write_u32::<LE>(self.file_size()).unwrap();
+        final_bytes.write_u32::<LE>(self.color_count()).unwrap();
 
-        // unknown data
-        final_bytes.write_u32::<LE>(self.unknown_data.0).unwrap();
-        final_bytes.write_u32::<LE>(self.unknown_data.1).unwrap();
-        final_bytes.write_u16::<LE>(self.unknown_data.2).unwrap();
-        final_bytes.write_u16::<LE>(self.unknown_data.3).unwrap();
+        final_bytes.write_u32::<LE>(self.header.unknown_10).unwrap();
+        final_bytes.write_u32::<LE>(self.header.unknown_14).unwrap();
+        final_bytes.write_u16::<LE>(self.header.unknown_18).unwrap();
+        final_bytes.write_u16::<LE>(self.header.unknown_1a).unwrap();
 
         // padding
         final_bytes.write_u32::<LE>(0x0).unwrap();
@@ -106,3 +180,89 @@ impl BBCFHpl {
         final_bytes
     }
 }
+
+/// Creates a new [`BBCFHpl`] from a list of colors
+pub struct BBCFHplBuilder {
+    version: u32,
+    header: BBCFHplHeader,
+    palette: Vec<RGBAColor>,
+}
+
+impl BBCFHplBuilder {
+    pub fn new(palette: Vec<RGBAColor>) -> Self {
+        Self {
+            version: DEFAULT_VERSION,
+            header: BBCFHplHeader::default(),
+            palette,
+        }
+    }
+
+    /// [`DEFAULT_VERSION`] by default
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// [`BBCFHplHeader::default`] by default
+    pub fn header(mut self, header: BBCFHplHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn build(self) -> BBCFHpl {
+        BBCFHpl {
+            version: self.version,
+            header: self.header,
+            palette: self.palette,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hpl_sizes() {
+        let color = RGBAColor {
+            red: 1,
+            green: 2,
+            blue: 3,
+            alpha: 4,
+        };
+        let hpl = BBCFHpl::builder(vec![color; 4]).build();
+        assert_eq!(hpl.version, DEFAULT_VERSION);
+        assert_eq!(
+            &hpl.to_bytes()[..0x20],
+            b"HPAL\x25\x01\0\0\x30\0\0\0\x04\0\0\0\0\0\0\0\0\0\0\0\x10\0\0\0\0\0\0\0"
+        );
+
+        let hpl = BBCFHpl::builder(vec![color; 4])
+            .version(3)
+            .header(BBCFHplHeader {
+                unknown_14: 7,
+                ..Default::default()
+            })
+            .build();
+        assert_eq!(hpl.file_size(), 0x30);
+
+        let bytes = hpl.to_bytes();
+        let parsed = BBCFHpl::parse(&bytes).unwrap();
+        assert_eq!(parsed.version, 3);
+        assert_eq!(parsed.color_count(), 4);
+        assert_eq!(parsed.header, hpl.header);
+        assert_eq!(parsed.palette, hpl.palette);
+
+        // declared size disagrees with the data
+        let mut wrong_size = bytes.clone();
+        wrong_size[0x8] += 4;
+        assert!(BBCFHpl::parse(&wrong_size).is_err());
+
+        // declared color count disagrees with the data
+        let mut wrong_count = bytes.clone();
+        wrong_count[0xC] = 5;
+        assert!(BBCFHpl::parse(&wrong_count).is_err());
+
+        assert!(BBCFHpl::parse(&bytes[..bytes.len() - 4]).is_err());
+    }
+}
@@ -9,4 +9,6 @@ pub enum Error {
     Rebuilder(String),
     #[error("Pac file has no entries")]
     NoPacEntries,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{arcsys_filename_hash, pac::Pac, Error};
+
+/// A collection of known filenames indexed by their [`arcsys_filename_hash`].
+/// Used to give names back to hash-only [`PacEntry`](crate::pac::PacEntry)s,
+/// like the ones found in `ID_ONLY` archives
+#[derive(Clone, Debug, Default)]
+pub struct NameDictionary {
+    names: HashMap<u32, Vec<String>>,
+}
+
+/// An entry that matched more than one name in a [`NameDictionary`]
+#[derive(Clone, Debug)]
+pub struct NameCollision {
+    /// Index into [`Pac::entries`]
+    pub entry: usize,
+    pub hash: u32,
+    pub candidates: Vec<String>,
+}
+
+/// Result of [`NameDictionary::resolve`]
+#[derive(Clone, Debug, Default)]
+pub struct ResolveReport {
+    /// Indices of entries that were given a name
+    pub resolved: Vec<usize>,
+    /// Entries left unnamed because several names share their hash
+    pub collisions: Vec<NameCollision>,
+    /// Indices of hash-only entries with no known name
+    pub unresolved: Vec<usize>,
+}
+
+impl NameDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a dictionary from a list of newline separated names.
+    /// Surrounding whitespace and empty lines are ignored
+    pub fn from_lines(text: &str) -> Self {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Build a dictionary from a JSON array of names
+    pub fn from_json(text: &str) -> Result<Self, Error> {
+        let names: Vec<String> =
+            serde_json::from_str(text).map_err(|e| Error::Parser(e.to_string()))?;
+
+        Ok(names.into_iter().collect())
+    }
+
+    /// Build a dictionary from either format, JSON is assumed if the text starts with `[`
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        if text.trim_start().starts_with('[') {
+            Self::from_json(text)
+        } else {
+            Ok(Self::from_lines(text))
+        }
+    }
+
+    /// Helper function to open a file and read a dictionary in either format
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+
+        Self::parse(&text)
+    }
+
+    /// Add a name to the dictionary, returns `false` if it was already known
+    pub fn insert(&mut self, name: impl Into<String>) -> bool {
+        let name = name.into();
+        let names = self.names.entry(arcsys_filename_hash(&name)).or_default();
+
+        if names.contains(&name) {
+            return false;
+        }
+
+        names.push(name);
+        true
+    }
+
+    /// All known names that produce this hash
+    pub fn get(&self, hash: u32) -> &[String] {
+        self.names.get(&hash).map_or(&[], |names| names.as_slice())
+    }
+
+    /// The amount of unique names in the dictionary
+    pub fn len(&self) -> usize {
+        self.names.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Every known name, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.names.values().flatten().map(String::as_str).collect();
+        names.sort_unstable();
+
+        names
+    }
+
+    /// Hashes that more than one known name maps to, sorted by hash
+    pub fn collisions(&self) -> Vec<(u32, &[String])> {
+        let mut collisions: Vec<(u32, &[String])> = self
+            .names
+            .iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(hash, names)| (*hash, names.as_slice()))
+            .collect();
+        collisions.sort_unstable_by_key(|(hash, _)| *hash);
+
+        collisions
+    }
+
+    /// Learn every filename from a named archive, returns how many names were new
+    pub fn harvest(&mut self, pac: &Pac) -> usize {
+        pac.entries
+            .iter()
+            .filter_map(|entry| entry.name())
+            .filter(|name| self.insert(*name))
+            .count()
+    }
+
+    /// Attach names to all hash-only entries of `pac` that have exactly one known name.
+    /// Entries that already have a name are left untouched
+    pub fn resolve(&self, pac: &mut Pac) -> ResolveReport {
+        let mut report = ResolveReport::default();
+
+        for (index, entry) in pac.entries.iter_mut().enumerate() {
+            if entry.name().is_some() {
+                continue;
+            }
+
+            let hash = entry.hash_id();
+            match self.get(hash) {
+                [] => report.unresolved.push(index),
+                [name] => {
+                    entry.set_name(name.clone());
+                    report.resolved.push(index);
+                }
+                candidates => report.collisions.push(NameCollision {
+                    entry: index,
+                    hash,
+                    candidates: candidates.to_vec(),
+                }),
+            }
+        }
+
+        report
+    }
+
+    /// Serialize the dictionary as newline separated names
+    pub fn to_lines(&self) -> String {
+        self.names()
+            .into_iter()
+            .fold(String::new(), |mut text, name| {
+                text.push_str(name);
+                text.push('\n');
+                text
+            })
+    }
+
+    /// Serialize the dictionary as a JSON array of names
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.names()).expect("names should serialize")
+    }
+}
+
+impl Extend<String> for NameDictionary {
+    fn extend<T: IntoIterator<Item = String>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|name| {
+            self.insert(name);
+        });
+    }
+}
+
+impl FromIterator<String> for NameDictionary {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        let mut dictionary = Self::new();
+        dictionary.extend(iter);
+
+        dictionary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pac::{Compression, PacEntry, PacStyle};
+
+    fn pac(entries: Vec<PacEntry>) -> Pac {
+        Pac {
+            compression: Compression::None,
+            pac_style: PacStyle::empty(),
+            entries,
+        }
+    }
+
+    #[test]
+    fn test_name_dictionary() {
+        let mut dictionary = NameDictionary::parse("es00.hip\n\n  xsoazy.hip \n").unwrap();
+        let named = pac(vec![
+            PacEntry::new_named("es00.hip".into(), vec![0]),
+            PacEntry::new_named("sivnae.hip".into(), vec![0]),
+        ]);
+        assert_eq!(dictionary.harvest(&named), 1);
+        assert!(!dictionary.insert("es00.hip"));
+        assert_eq!(dictionary.len(), 3);
+
+        // both names hash to the same value
+        let collision = arcsys_filename_hash("xsoazy.hip");
+        assert_eq!(collision, arcsys_filename_hash("sivnae.hip"));
+        assert_eq!(dictionary.collisions().len(), 1);
+        assert_eq!(dictionary.collisions()[0].0, collision);
+
+        let mut unnamed = pac(vec![
+            PacEntry::new_unnamed(arcsys_filename_hash("es00.hip"), vec![0]),
+            PacEntry::new_unnamed(collision, vec![0]),
+            PacEntry::new_unnamed(0x12345678, vec![0]),
+        ]);
+        let report = dictionary.resolve(&mut unnamed);
+        assert_eq!(report.resolved, vec![0]);
+        assert_eq!(unnamed.entries[0].name(), Some("es00.hip"));
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].entry, 1);
+        assert_eq!(report.collisions[0].candidates.len(), 2);
+        assert_eq!(unnamed.entries[1].name(), None);
+        assert_eq!(report.unresolved, vec![2]);
+
+        let json = NameDictionary::parse(&dictionary.to_json()).unwrap();
+        assert_eq!(json.names(), dictionary.names());
+        assert!(NameDictionary::from_json("[1, 2]").is_err());
+    }
+}
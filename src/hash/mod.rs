@@ -0,0 +1,11 @@
+//! Tools for recovering the filenames behind [`arcsys_filename_hash`](crate::arcsys_filename_hash) values
+
+mod dictionary;
+mod solver;
+mod template;
+
+pub use dictionary::{NameCollision, NameDictionary, ResolveReport};
+#[cfg(feature = "rayon")]
+pub use solver::par_invert;
+pub use solver::{invert, Preimages};
+pub use template::{Candidates, Template};
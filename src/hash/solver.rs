@@ -0,0 +1,344 @@
+//! Inverting [`arcsys_filename_hash`] by working backwards through known suffixes
+//! and meeting in the middle of the unknown part of the name.
+//!
+//! The hash is `h = c + 137 * h` over every byte, so `H(a + b) = H(a) * 137^len(b) + H(b)`.
+//! Since 137 is odd it has an inverse modulo 2^32, which lets a known suffix be peeled off the target hash directly.
+//! The unknown part is then split in two: every right half of a fixed length is hashed into a sorted table once,
+//! and each left half only needs a single table lookup to find all right halves that complete it.
+
+use std::ops::Range;
+
+use crate::{arcsys_filename_hash, Error};
+
+/// Upper bound on the amount of right halves hashed into a lookup table
+const TABLE_LIMIT: u64 = 1 << 22;
+
+/// Find every name made of `prefix`, up to `max_len` bytes from `alphabet`, then `suffix`,
+/// that hashes to `hash`. Names are yielded shortest first.
+///
+/// `alphabet` must only contain ASCII characters, duplicates are ignored.
+/// Fails if it doesn't, or if `max_len` is too long to enumerate at all.
+/// Past about 7 unknown characters most hashes have several preimages, so expect plenty of nonsense names
+pub fn invert(
+    hash: u32,
+    alphabet: &str,
+    max_len: usize,
+    prefix: &str,
+    suffix: &str,
+) -> Result<Preimages, Error> {
+    let solver = Solver::new(hash, alphabet, max_len, prefix, suffix)?;
+
+    Ok(Preimages {
+        table: solver.table(0),
+        solver,
+        max_len,
+        len: 0,
+        left: 0..1,
+        current_left: 0,
+        matches: 0..0,
+    })
+}
+
+/// Parallel version of [`invert`], yields the same names in no particular order
+#[cfg(feature = "rayon")]
+pub fn par_invert(
+    hash: u32,
+    alphabet: &str,
+    max_len: usize,
+    prefix: &str,
+    suffix: &str,
+) -> Result<impl rayon::iter::ParallelIterator<Item = String>, Error> {
+    use rayon::prelude::*;
+    use std::sync::Arc;
+
+    let solver = Arc::new(Solver::new(hash, alphabet, max_len, prefix, suffix)?);
+    let tables: Arc<Vec<SuffixTable>> = Arc::new(
+        (0..=max_len.min(solver.max_table_len()))
+            .into_par_iter()
+            .map(|len| solver.table(len))
+            .collect(),
+    );
+
+    Ok((0..=max_len).into_par_iter().flat_map(move |len| {
+        let solver = solver.clone();
+        let tables = tables.clone();
+        let table_len = len.min(solver.max_table_len());
+
+        (0..solver.left_halves(len - table_len))
+            .into_par_iter()
+            .flat_map_iter(move |left| {
+                let table = &tables[table_len];
+                let matches = solver.lookup(len, left, table);
+
+                matches
+                    .map(|i| solver.name(len - table_len, left, table.entries[i].1, table_len))
+                    .collect::<Vec<_>>()
+            })
+    }))
+}
+
+/// Iterator over the preimages of a hash, created by [`invert`]
+pub struct Preimages {
+    solver: Solver,
+    max_len: usize,
+    /// Length of the unknown part currently being searched
+    len: usize,
+    table: SuffixTable,
+    /// Left halves still left to check at this length
+    left: Range<u64>,
+    current_left: u64,
+    /// Table entries that complete `current_left`
+    matches: Range<usize>,
+}
+
+impl Iterator for Preimages {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(i) = self.matches.next() {
+                let table_len = self.table.len;
+
+                return Some(self.solver.name(
+                    self.len - table_len,
+                    self.current_left,
+                    self.table.entries[i].1,
+                    table_len,
+                ));
+            }
+
+            if let Some(left) = self.left.next() {
+                self.current_left = left;
+                self.matches = self.solver.lookup(self.len, left, &self.table);
+                continue;
+            }
+
+            if self.len == self.max_len {
+                return None;
+            }
+
+            self.len += 1;
+
+            let table_len = self.len.min(self.solver.max_table_len());
+            if table_len != self.table.len {
+                self.table = self.solver.table(table_len);
+            }
+
+            self.left = 0..self.solver.left_halves(self.len - table_len);
+        }
+    }
+}
+
+/// Every string of one length over the alphabet, sorted by hash
+struct SuffixTable {
+    len: usize,
+    /// (hash, index of the string)
+    entries: Vec<(u32, u32)>,
+}
+
+struct Solver {
+    alphabet: Vec<u8>,
+    prefix: String,
+    suffix: String,
+    prefix_hash: u32,
+    /// The hash the prefix and unknown part must produce on their own
+    unrolled_hash: u32,
+}
+
+impl Solver {
+    fn new(
+        hash: u32,
+        alphabet: &str,
+        max_len: usize,
+        prefix: &str,
+        suffix: &str,
+    ) -> Result<Self, Error> {
+        if !alphabet.is_ascii() {
+            return Err(Error::Parser(format!(
+                "alphabet `{alphabet}` must only contain ASCII"
+            )));
+        }
+
+        let mut unique = Vec::new();
+        for c in alphabet.bytes() {
+            if !unique.contains(&c) {
+                unique.push(c);
+            }
+        }
+
+        let suffix_len = suffix.len() as u32;
+        let unrolled_hash = hash
+            .wrapping_sub(arcsys_filename_hash(suffix))
+            .wrapping_mul(INVERSE_137.wrapping_pow(suffix_len));
+
+        let solver = Self {
+            alphabet: unique,
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            prefix_hash: arcsys_filename_hash(prefix),
+            unrolled_hash,
+        };
+
+        // every left half has to be indexable, which also keeps `chars` from overflowing
+        let left_len = max_len.saturating_sub(solver.max_table_len());
+        if (solver.alphabet.len() as u64)
+            .checked_pow(left_len as u32)
+            .is_none()
+        {
+            return Err(Error::Parser(format!(
+                "can't search {max_len} characters from an alphabet of {}",
+                solver.alphabet.len()
+            )));
+        }
+
+        Ok(solver)
+    }
+
+    /// Longest right half that fits in [`TABLE_LIMIT`]
+    fn max_table_len(&self) -> usize {
+        let base = self.alphabet.len() as u64;
+        if base <= 1 {
+            return usize::MAX;
+        }
+
+        let mut len = 0;
+        while base.pow(len as u32 + 1) <= TABLE_LIMIT {
+            len += 1;
+        }
+
+        len
+    }
+
+    /// Amount of left halves of `len` characters, [`Solver::new`] checks this fits
+    fn left_halves(&self, len: usize) -> u64 {
+        (self.alphabet.len() as u64).pow(len as u32)
+    }
+
+    fn table(&self, len: usize) -> SuffixTable {
+        let count = self.left_halves(len) as u32;
+        let mut entries: Vec<(u32, u32)> = (0..count)
+            .map(|index| (self.hash_of(index as u64, len), index))
+            .collect();
+        entries.sort_unstable();
+
+        SuffixTable { len, entries }
+    }
+
+    /// Table entries that complete the given left half into an unknown part of `len` characters
+    fn lookup(&self, len: usize, left: u64, table: &SuffixTable) -> Range<usize> {
+        let left_len = len - table.len;
+        let scale = 137u32.wrapping_pow(table.len as u32);
+
+        // H(prefix + left + right) = (H(prefix) * 137^left_len + H(left)) * 137^right_len + H(right)
+        let head = self
+            .prefix_hash
+            .wrapping_mul(137u32.wrapping_pow(left_len as u32))
+            .wrapping_add(self.hash_of(left, left_len));
+        let needed = self.unrolled_hash.wrapping_sub(head.wrapping_mul(scale));
+
+        let start = table.entries.partition_point(|(hash, _)| *hash < needed);
+        let end = table.entries.partition_point(|(hash, _)| *hash <= needed);
+
+        start..end
+    }
+
+    /// Hash of the string with the given index
+    fn hash_of(&self, index: u64, len: usize) -> u32 {
+        self.chars(index, len).fold(0u32, |hash, c| {
+            (c as u32).wrapping_add(137u32.wrapping_mul(hash))
+        })
+    }
+
+    /// Characters of the string with the given index, most significant digit first
+    fn chars(&self, index: u64, len: usize) -> impl Iterator<Item = u8> + '_ {
+        let base = self.alphabet.len() as u64;
+
+        (0..len).rev().map(move |digit| {
+            let place = base.pow(digit as u32);
+            self.alphabet[((index / place) % base) as usize]
+        })
+    }
+
+    fn name(&self, left_len: usize, left: u64, right: u32, right_len: usize) -> String {
+        let mut name = self.prefix.clone();
+        name.extend(self.chars(left, left_len).map(char::from));
+        name.extend(self.chars(right as u64, right_len).map(char::from));
+        name.push_str(&self.suffix);
+
+        name
+    }
+}
+
+/// Multiplicative inverse of 137 modulo 2^32
+const INVERSE_137: u32 = {
+    // newton's method, each step doubles the amount of correct low bits
+    let mut inverse = 137u32;
+    let mut i = 0;
+    while i < 5 {
+        inverse = inverse.wrapping_mul(2u32.wrapping_sub(137u32.wrapping_mul(inverse)));
+        i += 1;
+    }
+
+    inverse
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz0123456789_";
+
+    #[test]
+    fn test_inverse() {
+        assert_eq!(137u32.wrapping_mul(INVERSE_137), 1);
+    }
+
+    #[test]
+    fn test_invert() {
+        let hash = arcsys_filename_hash("vr_es_12.hpl");
+        let names: Vec<String> = invert(hash, ALPHABET, 3, "vr_", "_12.hpl")
+            .unwrap()
+            .collect();
+
+        assert!(names.contains(&"vr_es_12.hpl".to_string()));
+        assert!(names.iter().all(|name| arcsys_filename_hash(name) == hash));
+
+        // long enough to need both halves of the search
+        let hash = arcsys_filename_hash("ab_cdefg");
+        let names: Vec<String> = invert(hash, ALPHABET, 6, "ab_", "g").unwrap().collect();
+
+        assert!(names.contains(&"ab_cdefg".to_string()));
+        assert!(names.iter().all(|name| arcsys_filename_hash(name) == hash));
+
+        // too many left halves to count, and a non-ASCII alphabet
+        assert!(invert(hash, ALPHABET, 18, "", "").is_err());
+        assert!(invert(hash, "aé", 3, "", "").is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_invert() {
+        use rayon::iter::ParallelIterator;
+
+        for (name, max_len, prefix, suffix) in [
+            ("vr_es_12.hpl", 3, "vr_", "_12.hpl"),
+            ("ab_cdefg", 6, "ab_", "g"),
+        ] {
+            let hash = arcsys_filename_hash(name);
+
+            let mut names: Vec<String> = invert(hash, ALPHABET, max_len, prefix, suffix)
+                .unwrap()
+                .collect();
+            let mut par_names: Vec<String> = par_invert(hash, ALPHABET, max_len, prefix, suffix)
+                .unwrap()
+                .collect();
+            names.sort();
+            par_names.sort();
+
+            assert!(names.contains(&name.to_string()));
+            assert_eq!(par_names, names, "{name}");
+        }
+
+        assert!(par_invert(0, ALPHABET, 18, "", "").is_err());
+    }
+}
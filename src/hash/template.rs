@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{arcsys_filename_hash, hash::NameDictionary, Error};
+
+/// A filename pattern that expands into every candidate name it describes.
+///
+/// Pattern syntax:
+/// - plain text is copied as-is
+/// - `{0-15}` is an inclusive numeric range, `{00-99}` zero-pads every number to the width of the start
+/// - `{a|b|c}` picks one of several words
+/// - `{$name}` picks one word from a list supplied to [`Template::parse_with_lists`]
+/// - `{{` and `}}` are literal braces
+///
+/// e.g. `{$char}{00-99}_{000-999}.hip` or `vr_{$char}_{0-9}.hpl`
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+/// One piece of a [`Template`], numeric ranges are expanded lazily so huge ranges cost nothing up front
+#[derive(Clone, Debug)]
+enum Part {
+    Words(Vec<String>),
+    Range { first: u64, last: u64, width: usize },
+}
+
+impl Part {
+    fn len(&self) -> u128 {
+        match self {
+            Part::Words(words) => words.len() as u128,
+            Part::Range { first, last, .. } => (last - first) as u128 + 1,
+        }
+    }
+
+    /// Append the `index`th choice of this part to `name`
+    fn push_to(&self, name: &mut String, index: u128) {
+        use std::fmt::Write;
+
+        match self {
+            Part::Words(words) => name.push_str(&words[index as usize]),
+            Part::Range { first, width, .. } => {
+                let n = first + index as u64;
+                write!(name, "{n:0width$}").expect("writing to a string can't fail");
+            }
+        }
+    }
+}
+
+impl Template {
+    /// Parse a pattern that doesn't use any named lists
+    pub fn parse(pattern: &str) -> Result<Self, Error> {
+        Self::parse_with_lists(pattern, &HashMap::new())
+    }
+
+    /// Parse a pattern, resolving `{$name}` from `lists`
+    pub fn parse_with_lists(
+        pattern: &str,
+        lists: &HashMap<String, Vec<String>>,
+    ) -> Result<Self, Error> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut group = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => group.push(c),
+                            None => {
+                                return Err(Error::Parser(format!(
+                                    "unclosed `{{` in template `{pattern}`"
+                                )))
+                            }
+                        }
+                    }
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Words(vec![std::mem::take(&mut literal)]));
+                    }
+                    parts.push(parse_group(&group, lists)?);
+                }
+                '}' => {
+                    return Err(Error::Parser(format!(
+                        "unmatched `}}` in template `{pattern}`"
+                    )))
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Words(vec![literal]));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// The amount of names this template expands to
+    pub fn count(&self) -> u128 {
+        self.parts.iter().map(Part::len).product()
+    }
+
+    /// Every name this template expands to, with the last group changing fastest
+    pub fn candidates(&self) -> Candidates<'_> {
+        Candidates {
+            template: self,
+            indices: vec![0; self.parts.len()],
+            done: self.parts.iter().any(|part| part.len() == 0),
+        }
+    }
+
+    /// Collect every candidate whose hash is one of `targets`
+    pub fn recover(&self, targets: &HashSet<u32>) -> NameDictionary {
+        self.candidates()
+            .filter(|name| targets.contains(&arcsys_filename_hash(name)))
+            .collect()
+    }
+}
+
+fn parse_group(group: &str, lists: &HashMap<String, Vec<String>>) -> Result<Part, Error> {
+    if let Some(name) = group.strip_prefix('$') {
+        return lists
+            .get(name)
+            .cloned()
+            .map(Part::Words)
+            .ok_or_else(|| Error::Parser(format!("no list named `{name}` was supplied")));
+    }
+
+    if let Some((start, end)) = group.split_once('-') {
+        if let (Ok(first), Ok(last)) = (start.parse::<u64>(), end.parse::<u64>()) {
+            if first > last {
+                return Err(Error::Parser(format!("empty range `{{{group}}}`")));
+            }
+
+            let width = if start.len() > 1 && start.starts_with('0') {
+                start.len()
+            } else {
+                0
+            };
+
+            return Ok(Part::Range { first, last, width });
+        }
+    }
+
+    Ok(Part::Words(group.split('|').map(str::to_string).collect()))
+}
+
+/// Iterator over the names of a [`Template`], created by [`Template::candidates`]
+pub struct Candidates<'a> {
+    template: &'a Template,
+    indices: Vec<u128>,
+    done: bool,
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut name = String::new();
+        for (part, &index) in self.template.parts.iter().zip(&self.indices) {
+            part.push_to(&mut name, index);
+        }
+
+        // advance like an odometer, finishing once every digit has wrapped around
+        self.done = true;
+        for (index, part) in self
+            .indices
+            .iter_mut()
+            .zip(self.template.parts.iter())
+            .rev()
+        {
+            *index += 1;
+
+            if *index < part.len() {
+                self.done = false;
+                break;
+            }
+
+            *index = 0;
+        }
+
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let lists = HashMap::from([("char".to_string(), vec!["es".into(), "ha".into()])]);
+        let template = Template::parse_with_lists("{$char}{00-02}_{a|b}.hip", &lists).unwrap();
+
+        assert_eq!(template.count(), 12);
+
+        let names: Vec<String> = template.candidates().collect();
+        assert_eq!(names.len(), 12);
+        assert_eq!(names[0], "es00_a.hip");
+        assert_eq!(names[1], "es00_b.hip");
+        assert_eq!(names[11], "ha02_b.hip");
+
+        let targets = HashSet::from([arcsys_filename_hash("ha01_a.hip")]);
+        assert_eq!(template.recover(&targets).names(), vec!["ha01_a.hip"]);
+
+        let names: Vec<String> = Template::parse("{{{8-10}}}")
+            .unwrap()
+            .candidates()
+            .collect();
+        assert_eq!(names, vec!["{8}", "{9}", "{10}"]);
+
+        // ranges aren't expanded up front
+        let template = Template::parse("{0-18446744073709551615}").unwrap();
+        assert_eq!(template.count(), 1 << 64);
+        assert_eq!(template.candidates().nth(12345).unwrap(), "12345");
+    }
+}
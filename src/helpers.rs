@@ -142,24 +142,7 @@ pub(crate) fn parse_bgra(i: &[u8]) -> IResult<&[u8], RGBAColor> {
     ));
 }
 
-pub(crate) fn parse_argb(i: &[u8]) -> IResult<&[u8], RGBAColor> {
-    let (i, alpha) = le_u8(i)?;
-    let (i, red) = le_u8(i)?;
-    let (i, green) = le_u8(i)?;
-    let (i, blue) = le_u8(i)?;
-
-    return Ok((
-        i,
-        RGBAColor {
-            red,
-            green,
-            blue,
-            alpha,
-        },
-    ));
-}
-
-#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RGBAColor {
     pub red: u8,
     pub green: u8,
@@ -200,4 +183,21 @@ impl Palette for IndexedImage {
     fn get_palette(&self) -> Vec<RGBAColor> {
         self.palette.clone()
     }
+
+    /// Fails with more than 256 colors, which a u8 can't index
+    fn set_palette(&mut self, palette: Vec<RGBAColor>) -> Result<(), Error> {
+        if palette.len() > 256 {
+            return Err(Error::Rebuilder(format!(
+                "an indexed image holds up to 256 colors, got {}",
+                palette.len()
+            )));
+        }
+
+        self.palette = palette;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.palette.len()
+    }
 }
@@ -4,10 +4,11 @@
 mod error;
 mod helpers;
 mod traits;
+mod zlib;
 
 pub use binrw::BinRead;
 
-pub use crate::traits::{ParseFromBytes, Rebuild};
+pub use crate::traits::{Palette, ParseFromBytes, Rebuild};
 
 /// Blazblue Centralfiction
 pub mod bbcf;
@@ -15,11 +16,16 @@ pub mod bbcf;
 pub mod ggacpr;
 /// Guilty Gear STRIVE
 pub mod ggst;
+/// Filename hash utilities
+pub mod hash;
 /// PAC archive format found in most arcsys games.
 pub mod pac;
+/// Palette exchange formats and editing
+pub mod palette;
 
 pub use error::Error;
 pub use helpers::{arcsys_filename_hash, IndexedImage, RGBAColor};
+pub use zlib::ZlibSeekReader;
 
 #[cfg(test)]
 mod tests {
@@ -30,6 +36,26 @@ mod tests {
     use binrw::BinRead;
     use walkdir::WalkDir;
 
+    #[test]
+    fn test_hip() {
+        let hip_path = PathBuf::from(
+            env::var("ARCSYS_HIPS").expect("Must have ARCSYS_HIPS env variable set!"),
+        );
+
+        let report = crate::bbcf::hip::verify_roundtrip(hip_path).unwrap();
+
+        for failure in &report.failures {
+            println!("{:?}: {:?}", failure.path, failure.kind);
+        }
+
+        for (field, values) in &report.header_values {
+            println!("{field}: {values:x?}");
+        }
+
+        println!("checked {} HIPs", report.checked);
+        assert!(report.is_ok());
+    }
+
     #[test]
     fn test_pac() {
         let pac_path = PathBuf::from(
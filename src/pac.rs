@@ -1,44 +1,133 @@
 //! PAC archive format support for most modern arcsys fighters
 //! Currently rebuilds bit-perfect on most files with edge cases like [`PacStyle::PATH_CUT`]s hash function unsupported
 
-use std::io::{Cursor, Read, SeekFrom};
-
-use binrw::{binread, io::NoSeek, NullString};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Seek, SeekFrom};
+
+use binrw::{
+    binread,
+    io::NoSeek,
+    meta::{EndianKind, ReadEndian},
+    BinRead, BinResult, Endian, NullString,
+};
 use bitflags::bitflags;
 use byteorder::WriteBytesExt;
 use flate2::read::ZlibDecoder;
 
-use crate::{arcsys_filename_hash, helpers};
+use crate::{arcsys_filename_hash, helpers, Error, ZlibSeekReader};
 
 helpers::impl_open!(Pac);
 helpers::impl_open!(Zcmp);
 helpers::impl_open!(DfasFPac);
 
-#[binread]
-#[br(little)]
 #[derive(Clone, Debug)]
 pub struct Pac {
-    // hack to parse compressed pacs transparently
-    // without exposing any extra info in the public API
-    #[br(temp, map = |pac_reader: InternalPacReader| pac_reader.as_pac())]
-    _pac: InternalPac,
-
-    // actual struct fields:
-    #[br(calc = _pac.compression)]
     pub compression: Compression,
-    #[br(calc = _pac.pac_style)]
     pub pac_style: PacStyle,
-    #[br(calc = _pac.entries)]
     pub entries: Vec<PacEntry>,
 }
 
+// compressed PACs are parsed transparently through `PacReader`
+impl BinRead for Pac {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        _endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        PacReader::new(reader)?.into_pac()
+    }
+}
+
+impl ReadEndian for Pac {
+    const ENDIAN: EndianKind = EndianKind::Endian(Endian::Little);
+}
+
 impl Pac {
     pub fn to_bytes(&self) -> Vec<u8> {
-        fpac_to_bytes(self)
+        fpac_to_bytes(self, false).0
+    }
+
+    /// Rebuild the PAC with byte-identical entries pointing at a single stored copy of their data.
+    /// Returns the rebuilt bytes along with how many bytes were saved compared to [`Pac::to_bytes`].
+    ///
+    /// Fails if the [`PacStyle`] of this PAC doesn't allow shared offsets, see [`PacStyle::allows_shared_offsets`]
+    pub fn to_bytes_deduplicated(&self) -> Result<(Vec<u8>, usize), Error> {
+        if !self.pac_style.allows_shared_offsets() {
+            return Err(Error::Rebuilder(format!(
+                "PAC style {:?} does not allow shared data offsets",
+                self.pac_style
+            )));
+        }
+
+        Ok(fpac_to_bytes(self, true))
+    }
+
+    /// Group all entries that have byte-identical contents
+    pub fn find_duplicates(&self) -> DuplicateReport {
+        let mut groups: HashMap<&[u8], Vec<usize>> = HashMap::new();
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            groups.entry(&entry.contents).or_default().push(index);
+        }
+
+        let mut groups: Vec<DuplicateGroup> = groups
+            .into_iter()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|(contents, entries)| {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                contents.hash(&mut hasher);
+
+                DuplicateGroup {
+                    hash: hasher.finish(),
+                    size: contents.len(),
+                    entries,
+                }
+            })
+            .collect();
+
+        // HashMap iteration order is random, keep the report stable
+        groups.sort_by_key(|group| group.entries[0]);
+
+        DuplicateReport { groups }
+    }
+}
+
+/// Entries of a [`Pac`] that share the exact same contents
+#[derive(Clone, Debug)]
+pub struct DuplicateGroup {
+    /// Hash of the shared contents
+    pub hash: u64,
+    /// Size of the shared contents in bytes
+    pub size: usize,
+    /// Indices into [`Pac::entries`], in ascending order
+    pub entries: Vec<usize>,
+}
+
+impl DuplicateGroup {
+    /// Bytes taken up by every copy of the contents except the first
+    pub fn redundant_bytes(&self) -> usize {
+        self.size * (self.entries.len() - 1)
+    }
+}
+
+/// Result of [`Pac::find_duplicates`]
+#[derive(Clone, Debug, Default)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+}
+
+impl DuplicateReport {
+    /// Total size of all redundant copies, not counting alignment padding
+    pub fn redundant_bytes(&self) -> usize {
+        self.groups.iter().map(|g| g.redundant_bytes()).sum()
     }
 }
 
-fn fpac_to_bytes(pac: &Pac) -> Vec<u8> {
+/// Returns the rebuilt PAC and the amount of data bytes skipped by sharing offsets
+fn fpac_to_bytes(pac: &Pac, share_offsets: bool) -> (Vec<u8>, usize) {
     use std::io::Write;
 
     use byteorder::LE;
@@ -72,7 +161,23 @@ fn fpac_to_bytes(pac: &Pac) -> Vec<u8> {
 
     let entry_count = pac.entries.len();
 
+    let data_alignment = if pac.pac_style.contains(PacStyle::ID_ONLY) {
+        0x4
+    } else {
+        0x10
+    };
+
+    // contents already written to the file section and their offset
+    let mut written: HashMap<&[u8], u32> = HashMap::new();
+    let mut saved = 0;
+
     for (entry_index, entry) in pac.entries.iter().enumerate() {
+        let shared_offset = if share_offsets {
+            written.get(entry.contents.as_slice()).copied()
+        } else {
+            None
+        };
+
         // write header contents
 
         if !pac.pac_style.contains(PacStyle::ID_ONLY) {
@@ -86,7 +191,7 @@ fn fpac_to_bytes(pac: &Pac) -> Vec<u8> {
         meta_buffer.write_u32::<LE>(entry_index as u32).unwrap();
         // offset in the file section
         meta_buffer
-            .write_u32::<LE>(file_buffer.len() as u32)
+            .write_u32::<LE>(shared_offset.unwrap_or(file_buffer.len() as u32))
             .unwrap();
         meta_buffer
             .write_u32::<LE>(entry.contents.len() as u32)
@@ -108,14 +213,17 @@ fn fpac_to_bytes(pac: &Pac) -> Vec<u8> {
 
         (0..padding).for_each(|_| meta_buffer.write_u8(0).unwrap());
 
+        if shared_offset.is_some() {
+            saved += helpers::pad_to_nearest(entry.contents.len(), data_alignment);
+            continue;
+        }
+
+        written.insert(&entry.contents, file_buffer.len() as u32);
+
         // write file contents and pad
         file_buffer.write_all(&entry.contents).unwrap();
 
-        let padding = if pac.pac_style.contains(PacStyle::ID_ONLY) {
-            helpers::needed_to_align(file_buffer.len(), 0x4)
-        } else {
-            helpers::needed_to_align(file_buffer.len(), 0x10)
-        };
+        let padding = helpers::needed_to_align(file_buffer.len(), data_alignment);
         (0..padding).for_each(|_| file_buffer.write_u8(0).unwrap());
     }
 
@@ -135,109 +243,311 @@ fn fpac_to_bytes(pac: &Pac) -> Vec<u8> {
 
     buffer.append(&mut file_buffer);
 
-    buffer
+    (buffer, saved)
 }
 
+/// Standalone ZCMP compression wrapper, usually containing a PAC
 #[binread]
 #[br(magic = b"ZCMP", little)]
+#[derive(Clone, Debug)]
 pub struct Zcmp {
-    #[br(temp)]
-    original_size: u32,
-    #[br(temp, align_after = 0x10)]
-    _compressed_size: u32,
+    /// Decompressed size from the header, recalculated by [`Zcmp::to_bytes`]
+    pub original_size: u32,
+    /// Compressed size from the header, recalculated by [`Zcmp::to_bytes`]
+    #[br(align_after = 0x10)]
+    pub compressed_size: u32,
     #[br(count = original_size, map_stream = |reader| NoSeek::new(ZlibDecoder::new(reader)) )]
     pub data: Vec<u8>,
 }
 
+impl Zcmp {
+    /// Wrap some data, the compressed size stays `0` until the data is compressed by [`Zcmp::to_bytes`]
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            original_size: data.len() as u32,
+            compressed_size: 0,
+            data,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        compress_wrapped(b"ZCMP", &self.data)
+    }
+}
+
+/// Standalone DFASFPAC compression wrapper, usually containing a PAC
 #[binread]
 #[br(magic = b"DFASFPAC", little)]
+#[derive(Clone, Debug)]
 pub struct DfasFPac {
-    #[br(temp)]
-    original_size: u32,
-    #[br(temp, align_after = 0x10)]
-    _compressed_size: u32,
+    /// Decompressed size from the header, recalculated by [`DfasFPac::to_bytes`]
+    pub original_size: u32,
+    /// Compressed size from the header, recalculated by [`DfasFPac::to_bytes`]
+    #[br(align_after = 0x10)]
+    pub compressed_size: u32,
     #[br(count = original_size, map_stream = |reader| NoSeek::new(ZlibDecoder::new(reader)) )]
     pub data: Vec<u8>,
 }
 
-#[binread]
-#[derive(Clone, Debug)]
-#[br(little)]
-enum InternalPacReader {
-    Uncompressed(#[br(args(Compression::None))] InternalPac),
-    #[br(magic = b"ZCMP")]
-    Zcmp(
-        // original size
-        #[br(temp)] u32,
-        // compressed size
-        #[br(temp, align_after = 0x10)] u32,
-        #[br(
-            // awful hack because ZlibDecoder doesnt implement Seek
-            // and NoSeek doesn't allow us to align or pad.
-            // maybe implement some fake caching thing that emulates Seek over the stream?
-            map_stream = |reader| {
-                let mut decoder = ZlibDecoder::new(reader);
-                let mut buf: Vec<u8> = Vec::new();
-                decoder.read_to_end(&mut buf).expect("decoder should read to end");
-                Cursor::new(buf)
+impl DfasFPac {
+    /// Wrap some data, the compressed size stays `0` until the data is compressed by [`DfasFPac::to_bytes`]
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            original_size: data.len() as u32,
+            compressed_size: 0,
+            data,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        compress_wrapped(b"DFASFPAC", &self.data)
+    }
+}
+
+fn compress_wrapped(magic: &[u8], data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    use byteorder::LE;
+    use flate2::write::ZlibEncoder;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // File structure:
+    // 00..N magic
+    // N original size
+    // N+4 compressed size
+    // N+8 null padding to nearest 0x10
+    // ...  zlib stream
+    let mut buffer = Vec::new();
+
+    buffer.write_all(magic).unwrap();
+    buffer.write_u32::<LE>(data.len() as u32).unwrap();
+    buffer.write_u32::<LE>(compressed.len() as u32).unwrap();
+
+    let padding = helpers::needed_to_align(buffer.len(), 0x10);
+    (0..padding).for_each(|_| buffer.write_u8(0).unwrap());
+
+    buffer.extend(compressed);
+
+    buffer
+}
+
+/// Reads the entry list of a PAC up front, then reads entry contents on demand.
+///
+/// Compressed PACs are decompressed lazily through a [`ZlibSeekReader`](crate::ZlibSeekReader),
+/// so reading entries in order of their offset avoids inflating the same data twice
+pub struct PacReader<R> {
+    stream: PacStream<R>,
+    compression: Compression,
+    pac_style: PacStyle,
+    data_start: u64,
+    entries: Vec<PacEntryInfo>,
+}
+
+impl PacReader<std::io::BufReader<std::fs::File>> {
+    /// Helper function to open a file and read the entry list
+    pub fn open(path: impl AsRef<std::path::Path>) -> BinResult<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path.as_ref())?);
+
+        Self::new(file)
+    }
+}
+
+impl<R: Read + Seek> PacReader<R> {
+    /// Read the header and entry list of a PAC starting at the current position of `reader`
+    pub fn new(mut reader: R) -> BinResult<Self> {
+        use byteorder::{ReadBytesExt, LE};
+
+        let base = reader.stream_position()?;
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        // Compressed file structure:
+        // 00 magic b"ZCMP" or b"DFASFPAC"
+        // N original size
+        // N+4 compressed size
+        // 10 zlib stream containing an FPAC
+        let compression = match &magic {
+            b"FPAC" => Compression::None,
+            b"ZCMP" => Compression::Zcmp,
+            b"DFAS" => {
+                reader.read_exact(&mut magic)?;
+                if &magic != b"FPAC" {
+                    return Err(binrw::Error::BadMagic {
+                        pos: base + 4,
+                        found: Box::new(magic),
+                    });
+                }
+
+                Compression::DfasFPac
+            }
+            b"TXAC" => {
+                return Err(binrw::Error::AssertFail {
+                    pos: base,
+                    message: "TXAC archives are unsupported".into(),
+                })
             }
-        )]
-        #[br(args(Compression::Zcmp))]
-        InternalPac,
-    ),
-    #[br(magic = b"DFASFPAC")]
-    DfasFPac(
-        #[br(temp)] u32,
-        #[br(temp)] u32,
-        #[br(
-        // same hack again
-            map_stream = |reader| {
-                let mut decoder = ZlibDecoder::new(reader);
-                let mut buf: Vec<u8> = Vec::new();
-                decoder.read_to_end(&mut buf).expect("decoder should read to end");
-                Cursor::new(buf)
+            _ => {
+                return Err(binrw::Error::BadMagic {
+                    pos: base,
+                    found: Box::new(magic),
+                })
             }
-        )]
-        #[br(args(Compression::DfasFPac))]
-        InternalPac,
-    ),
-    #[br(magic = b"TXAC", pre_assert(false))]
-    Txac,
+        };
+
+        let mut stream = if compression == Compression::None {
+            let _data_start = reader.read_u32::<LE>()?;
+            let size = reader.read_u32::<LE>()? as u64;
+            reader.seek(SeekFrom::Start(base))?;
+
+            PacStream::Uncompressed { reader, base, size }
+        } else {
+            let original_size = reader.read_u32::<LE>()?;
+            let _compressed_size = reader.read_u32::<LE>()?;
+            reader.seek(SeekFrom::Start(base + 0x10))?;
+
+            PacStream::Compressed(ZlibSeekReader::new(reader, original_size as u64)?)
+        };
+
+        let header = PacHeader::read(&mut stream)?;
+
+        Ok(Self {
+            stream,
+            compression,
+            pac_style: header.pac_style,
+            data_start: header.data_start as u64,
+            entries: header.entries,
+        })
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn pac_style(&self) -> PacStyle {
+        self.pac_style
+    }
+
+    /// Metadata of every entry, in the order they appear in the PAC
+    pub fn entries(&self) -> &[PacEntryInfo] {
+        &self.entries
+    }
+
+    /// Read the entry at `index` of [`PacReader::entries`]
+    pub fn read_entry(&mut self, index: usize) -> BinResult<PacEntry> {
+        let info = self
+            .entries
+            .get(index)
+            .ok_or_else(|| binrw::Error::AssertFail {
+                pos: self.data_start,
+                message: format!("PAC has no entry {index}, it has {}", self.entries.len()),
+            })?;
+
+        let pos = self.data_start + info.offset as u64;
+        let mut contents = vec![0; info.size as usize];
+
+        self.stream
+            .seek(SeekFrom::Start(pos))
+            .and_then(|_| self.stream.read_exact(&mut contents))
+            .map_err(|e| binrw::Error::Custom {
+                pos,
+                err: Box::new(e),
+            })?;
+
+        Ok(PacEntry {
+            identifier: info.identifier.clone(),
+            contents,
+        })
+    }
+
+    /// Read every entry into a [`Pac`]
+    pub fn into_pac(mut self) -> BinResult<Pac> {
+        // read in order of the data to keep decompression moving forwards
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by_key(|i| self.entries[*i].offset);
+
+        let mut entries: Vec<Option<PacEntry>> = vec![None; self.entries.len()];
+        for index in order {
+            entries[index] = Some(self.read_entry(index)?);
+        }
+
+        Ok(Pac {
+            compression: self.compression,
+            pac_style: self.pac_style,
+            entries: entries.into_iter().flatten().collect(),
+        })
+    }
+}
+
+/// The stream containing the FPAC data itself, hiding any compression
+enum PacStream<R> {
+    /// `base` is where the FPAC starts in `reader`, `size` is its total size from the header
+    Uncompressed {
+        reader: R,
+        base: u64,
+        size: u64,
+    },
+    Compressed(ZlibSeekReader<R>),
+}
+
+impl<R: Read + Seek> Read for PacStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            PacStream::Uncompressed { reader, .. } => reader.read(buf),
+            PacStream::Compressed(reader) => reader.read(buf),
+        }
+    }
 }
 
-impl InternalPacReader {
-    fn as_pac(self) -> InternalPac {
+impl<R: Read + Seek> Seek for PacStream<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         match self {
-            Self::Uncompressed(p) => p,
-            Self::Zcmp(p) => p,
-            Self::DfasFPac(p) => p,
-            Self::Txac => panic!("TXAC is unsupported!"),
+            PacStream::Uncompressed { reader, base, size } => {
+                // checked before seeking so a bad seek leaves the reader where it was
+                let target = match pos {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::Current(offset) => {
+                        (reader.stream_position()? - *base).checked_add_signed(offset)
+                    }
+                    SeekFrom::End(offset) => size.checked_add_signed(offset),
+                };
+                let absolute = target.and_then(|target| base.checked_add(target));
+
+                let (Some(target), Some(absolute)) = (target, absolute) else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "seek to a position outside of the PAC",
+                    ));
+                };
+
+                reader.seek(SeekFrom::Start(absolute))?;
+                Ok(target)
+            }
+            PacStream::Compressed(reader) => reader.seek(pos),
         }
     }
 }
 
 #[binread]
-#[derive(Clone, Debug)]
 #[br(magic = b"FPAC", little)]
-#[br(import(compression: Compression))]
-struct InternalPac {
-    #[br(calc = compression)]
-    pub compression: Compression,
-    #[br(temp)]
+struct PacHeader {
     data_start: u32,
     #[br(temp)]
     _total_size: u32,
     #[br(temp)]
     file_count: u32,
     #[br(map = |x: u32| PacStyle::from_bits_retain(x))]
-    pub pac_style: PacStyle,
+    pac_style: PacStyle,
     #[br(temp, align_after = 0x10)]
     string_size: u32,
     #[br(args {
         count: file_count as usize,
-        inner: (pac_style, string_size, data_start),
+        inner: (pac_style, string_size),
     })]
-    pub entries: Vec<PacEntry>,
+    entries: Vec<PacEntryInfo>,
 }
 
 #[derive(Clone)]
@@ -246,10 +556,27 @@ enum EntryIdentifier {
     Hash(u32),
 }
 
+impl EntryIdentifier {
+    fn hash(&self) -> u32 {
+        match self {
+            EntryIdentifier::Name(name) => arcsys_filename_hash(name),
+            EntryIdentifier::Hash(hash) => *hash,
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            EntryIdentifier::Name(name) => Some(name),
+            EntryIdentifier::Hash(_) => None,
+        }
+    }
+}
+
+/// The metadata of a [`PacEntry`], read by [`PacReader`] without the entry contents
 #[binread]
 #[derive(Clone)]
-#[br(import(pac_style: PacStyle, string_size: u32, data_start: u32))]
-pub struct PacEntry {
+#[br(import(pac_style: PacStyle, string_size: u32))]
+pub struct PacEntryInfo {
     #[br(
         temp,
         if(!pac_style.intersects(PacStyle::ID_ONLY) && string_size > 0),
@@ -259,15 +586,50 @@ pub struct PacEntry {
     name: Option<String>,
     #[br(temp)]
     _id: u32,
-    #[br(temp)]
-    file_offset: u32,
-    #[br(temp)]
-    file_size: u32,
+    offset: u32,
+    size: u32,
     #[br(temp, align_after = 0x10)]
     hash: u32,
     #[br(calc(name.map(|x| EntryIdentifier::Name(x)).unwrap_or(EntryIdentifier::Hash(hash))))]
     identifier: EntryIdentifier,
-    #[br(count = file_size, restore_position, seek_before = SeekFrom::Start((data_start + file_offset) as u64))]
+}
+
+impl PacEntryInfo {
+    /// Get the hash identifier for the entry, usually a hash of the filename, unkown for ID_ONLY pacs
+    pub fn hash_id(&self) -> u32 {
+        self.identifier.hash()
+    }
+
+    /// Get the filename of the entry, returns None if the entry is for an ID_ONLY pac
+    pub fn name(&self) -> Option<&str> {
+        self.identifier.name()
+    }
+
+    /// Offset of the entry contents from the start of the data section
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Size of the entry contents
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+impl std::fmt::Debug for PacEntryInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PacEntryInfo")
+            .field("name", &format_args!("{:?}", self.name()))
+            .field("hash", &format_args!("0x{:0>8X}", self.hash_id()))
+            .field("offset", &format_args!("0x{:X}", self.offset))
+            .field("file_size", &self.size)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct PacEntry {
+    identifier: EntryIdentifier,
     pub contents: Vec<u8>,
 }
 
@@ -289,19 +651,17 @@ impl PacEntry {
 
     /// Get the hash identifier for the entry, usually a hash of the filename, unkown for ID_ONLY pacs
     pub fn hash_id(&self) -> u32 {
-        match self.identifier {
-            EntryIdentifier::Name(ref name) => arcsys_filename_hash(name),
-            EntryIdentifier::Hash(hash) => hash,
-        }
+        self.identifier.hash()
+    }
+
+    /// Set the filename of the entry, the hash identifier will be derived from it from now on
+    pub fn set_name(&mut self, name: String) {
+        self.identifier = EntryIdentifier::Name(name);
     }
 
     /// Get the filename of the entry, returns None if the entry is for an ID_ONLY pac
     pub fn name(&self) -> Option<&str> {
-        if let EntryIdentifier::Name(ref name) = self.identifier {
-            return Some(name);
-        }
-
-        None
+        self.identifier.name()
     }
 }
 
@@ -338,6 +698,15 @@ bitflags! {
     }
 }
 
+impl PacStyle {
+    /// Whether multiple entries may point at the same data offset.
+    /// Named entries are always located through their own offset and size,
+    /// but nothing confirms the same holds for `ID_ONLY` archives, so sharing is refused for them.
+    pub fn allows_shared_offsets(&self) -> bool {
+        !self.contains(PacStyle::ID_ONLY)
+    }
+}
+
 /// The compression wrapper used on the pac.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Compression {
@@ -348,3 +717,100 @@ pub enum Compression {
     /// Found in Blazblue Centralfiction
     DfasFPac,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinRead;
+
+    use super::*;
+
+    #[test]
+    fn test_deduplicated_rebuild() {
+        let pac = Pac {
+            compression: Compression::None,
+            pac_style: PacStyle::PATH_CUT | PacStyle::HASH_SORT | PacStyle::VERSION2,
+            entries: vec![
+                PacEntry::new_named("a.bin".into(), vec![1u8; 0x21]),
+                PacEntry::new_named("b.bin".into(), vec![2u8; 0x8]),
+                PacEntry::new_named("c.bin".into(), vec![1u8; 0x21]),
+            ],
+        };
+
+        let report = pac.find_duplicates();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].entries, vec![0, 2]);
+        assert_eq!(report.redundant_bytes(), 0x21);
+
+        let (bytes, saved) = pac.to_bytes_deduplicated().unwrap();
+        assert_eq!(saved, 0x30);
+        assert_eq!(bytes.len() + saved, pac.to_bytes().len());
+
+        let rebuilt = Pac::read(&mut Cursor::new(bytes)).unwrap();
+        for (original, rebuilt) in pac.entries.iter().zip(rebuilt.entries.iter()) {
+            assert_eq!(original.name(), rebuilt.name());
+            assert_eq!(original.contents, rebuilt.contents);
+        }
+    }
+
+    #[test]
+    fn test_compression_wrappers() {
+        let data: Vec<u8> = (0..0x1000u32).map(|x| (x % 7) as u8).collect();
+
+        let bytes = Zcmp::new(data.clone()).to_bytes();
+        let zcmp = Zcmp::read(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(zcmp.data, data);
+        assert_eq!(zcmp.original_size as usize, data.len());
+        assert_eq!(zcmp.compressed_size as usize, bytes.len() - 0x10);
+
+        let bytes = DfasFPac::new(data.clone()).to_bytes();
+        let dfasfpac = DfasFPac::read(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(dfasfpac.data, data);
+        assert_eq!(dfasfpac.compressed_size as usize, bytes.len() - 0x10);
+    }
+
+    #[test]
+    fn test_pac_reader_errors() {
+        let pac = Pac {
+            compression: Compression::None,
+            pac_style: PacStyle::empty(),
+            entries: vec![PacEntry::new_named("a.bin".into(), vec![1u8; 0x10])],
+        };
+
+        // the PAC sits between some unrelated data
+        let pac_bytes = pac.to_bytes();
+        let mut bytes = vec![0xFF; 0x8];
+        bytes.extend(&pac_bytes);
+        bytes.extend([0xEE; 0x8]);
+        let mut cursor = Cursor::new(&bytes);
+        cursor.set_position(0x8);
+
+        let mut reader = PacReader::new(cursor).unwrap();
+        assert_eq!(reader.stream.seek(SeekFrom::Start(0x4)).unwrap(), 0x4);
+        // still inside the reader but before the PAC, the position is left as it was
+        assert!(reader.stream.seek(SeekFrom::Current(-0x6)).is_err());
+        assert_eq!(reader.stream.stream_position().unwrap(), 0x4);
+        assert_eq!(
+            reader.stream.seek(SeekFrom::End(0)).unwrap(),
+            pac_bytes.len() as u64
+        );
+        assert_eq!(reader.read_entry(0).unwrap().contents, vec![1u8; 0x10]);
+
+        assert!(matches!(
+            reader.read_entry(1),
+            Err(binrw::Error::AssertFail { pos, .. }) if pos == reader.data_start
+        ));
+
+        // the entry's data was cut off
+        let cut = &bytes[..0x8 + pac_bytes.len() - 1];
+        let mut cursor = Cursor::new(cut);
+        cursor.set_position(0x8);
+        let mut reader = PacReader::new(cursor).unwrap();
+        let offset = reader.data_start + reader.entries()[0].offset() as u64;
+        assert!(matches!(
+            reader.read_entry(0),
+            Err(binrw::Error::Custom { pos, .. }) if pos == offset
+        ));
+    }
+}
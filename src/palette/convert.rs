@@ -0,0 +1,153 @@
+//! [`Palette`] implementations and conversions between the palette formats.
+//!
+//! GGXX stores colors as little-endian RGBA words with PS2 alpha, where 0x80 is opaque.
+//! Converting a GGXX palette to 8-bit alpha and back is lossless for alphas up to 0x80,
+//! the other way odd alphas are rounded up to the next even value
+
+use crate::{
+    bbcf::{
+        hip::{BBCFHip, BBCFHipImage},
+        hpl::BBCFHpl,
+    },
+    ggacpr::obj::GGXXPaletteEntry,
+    helpers::RGBAColor,
+    traits::Palette,
+    Error,
+};
+
+/// Colors in a GGXX palette
+pub const GGXX_PALETTE_SIZE: usize = 256;
+
+/// Convert a GGXX color, scaling its PS2 alpha from 0..=0x80 to 0..=0xFF
+pub fn from_ps2_color(color: u32) -> RGBAColor {
+    let [red, green, blue, alpha] = color.to_le_bytes();
+
+    RGBAColor {
+        red,
+        green,
+        blue,
+        alpha: (alpha as u16 * 2).min(0xFF) as u8,
+    }
+}
+
+/// Convert a color to a GGXX color, scaling its alpha from 0..=0xFF to 0..=0x80
+pub fn to_ps2_color(color: RGBAColor) -> u32 {
+    let alpha = color.alpha.div_ceil(2);
+    u32::from_le_bytes([color.red, color.green, color.blue, alpha])
+}
+
+impl Palette for BBCFHpl {
+    fn get_palette(&self) -> Vec<RGBAColor> {
+        self.palette.clone()
+    }
+
+    fn set_palette(&mut self, palette: Vec<RGBAColor>) -> Result<(), Error> {
+        self.palette = palette;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.palette.len()
+    }
+}
+
+/// The palette of an indexed sprite, other sprites have an empty palette that can't be set
+impl Palette for BBCFHip {
+    fn get_palette(&self) -> Vec<RGBAColor> {
+        match &self.image {
+            BBCFHipImage::Indexed { data, .. } => data.get_palette(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn set_palette(&mut self, palette: Vec<RGBAColor>) -> Result<(), Error> {
+        match &mut self.image {
+            BBCFHipImage::Indexed { data, .. } => data.set_palette(palette),
+            _ => Err(Error::Rebuilder(
+                "only indexed images have a palette".into(),
+            )),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.image {
+            BBCFHipImage::Indexed { data, .. } => data.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// See [`from_ps2_color`] for how colors are converted.
+/// Setting the palette fails unless it has exactly [`GGXX_PALETTE_SIZE`] colors
+impl Palette for GGXXPaletteEntry {
+    fn get_palette(&self) -> Vec<RGBAColor> {
+        self.palette.iter().map(|&c| from_ps2_color(c)).collect()
+    }
+
+    fn set_palette(&mut self, palette: Vec<RGBAColor>) -> Result<(), Error> {
+        if palette.len() != GGXX_PALETTE_SIZE {
+            return Err(Error::Rebuilder(format!(
+                "GGXX palettes have {GGXX_PALETTE_SIZE} colors, got {}",
+                palette.len()
+            )));
+        }
+
+        self.palette = palette.into_iter().map(to_ps2_color).collect();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.palette.len()
+    }
+}
+
+/// An HPL with the colors of a GGXX palette and a default header.
+/// Set the colors of an existing [`GGXXPaletteEntry`] with [`Palette::set_palette`] to go back
+impl From<&GGXXPaletteEntry> for BBCFHpl {
+    fn from(entry: &GGXXPaletteEntry) -> Self {
+        BBCFHpl::builder(entry.get_palette()).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ggxx_palette_conversion() {
+        let mut entry = GGXXPaletteEntry {
+            unk: 1,
+            unk1: 0,
+            unk2: 0,
+            unk3: 0,
+            unk4: 0,
+            unk5: 0,
+            unk6: 0,
+            unk7: 0,
+            palette: (0..GGXX_PALETTE_SIZE as u32)
+                .map(|i| u32::from_le_bytes([i as u8, 2, 3, (i % 0x81) as u8]))
+                .collect(),
+        };
+        let original = entry.palette.clone();
+
+        let hpl = BBCFHpl::from(&entry);
+        assert_eq!(hpl.len(), GGXX_PALETTE_SIZE);
+        assert_eq!(
+            hpl.palette[0x80],
+            RGBAColor {
+                red: 0x80,
+                green: 2,
+                blue: 3,
+                alpha: 0xFF,
+            },
+            "0x80 is opaque"
+        );
+        assert_eq!(hpl.palette[0x40].alpha, 0x80);
+
+        entry.set_palette(hpl.get_palette()).unwrap();
+        assert_eq!(entry.palette, original);
+        assert_eq!(entry.unk, 1);
+
+        assert!(entry.set_palette(vec![hpl.palette[0]; 16]).is_err());
+    }
+}
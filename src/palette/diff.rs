@@ -0,0 +1,142 @@
+use crate::{helpers::RGBAColor, traits::Palette, Error};
+
+/// An entry that differs between two palettes, see [`diff`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorChange {
+    pub index: usize,
+    /// `None` if the entry is past the end of the first palette
+    pub before: Option<RGBAColor>,
+    /// `None` if the entry is past the end of the second palette
+    pub after: Option<RGBAColor>,
+}
+
+/// An entry both sides of a [`merge`] changed in different ways
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergeConflict {
+    pub index: usize,
+    pub base: RGBAColor,
+    pub ours: RGBAColor,
+    pub theirs: RGBAColor,
+}
+
+/// Result of [`merge`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteMerge {
+    /// The merged colors, conflicting entries keep their color from the base palette
+    pub palette: Vec<RGBAColor>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl PaletteMerge {
+    /// Whether every entry merged without conflicts
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// List every entry that changed from `before` to `after`, including entries only one of them has
+pub fn diff(before: &impl Palette, after: &impl Palette) -> Vec<ColorChange> {
+    let (before, after) = (before.get_palette(), after.get_palette());
+
+    (0..before.len().max(after.len()))
+        .map(|index| ColorChange {
+            index,
+            before: before.get(index).copied(),
+            after: after.get(index).copied(),
+        })
+        .filter(|change| change.before != change.after)
+        .collect()
+}
+
+/// Merge the changes `ours` and `theirs` each made to `base`.
+///
+/// An entry changed on only one side takes that change, an entry changed to
+/// different colors on both sides is reported as a conflict.
+/// All three palettes have to be the same length
+pub fn merge(
+    base: &impl Palette,
+    ours: &impl Palette,
+    theirs: &impl Palette,
+) -> Result<PaletteMerge, Error> {
+    let (base, ours, theirs) = (base.get_palette(), ours.get_palette(), theirs.get_palette());
+
+    if base.len() != ours.len() || base.len() != theirs.len() {
+        return Err(Error::Rebuilder(format!(
+            "can't merge palettes of {}, {} and {} colors",
+            base.len(),
+            ours.len(),
+            theirs.len()
+        )));
+    }
+
+    let mut conflicts = Vec::new();
+    let palette = base
+        .iter()
+        .zip(ours.iter().zip(&theirs))
+        .enumerate()
+        .map(|(index, (&base, (&ours, &theirs)))| {
+            if ours == theirs || theirs == base {
+                ours
+            } else if ours == base {
+                theirs
+            } else {
+                conflicts.push(MergeConflict {
+                    index,
+                    base,
+                    ours,
+                    theirs,
+                });
+                base
+            }
+        })
+        .collect();
+
+    Ok(PaletteMerge { palette, conflicts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbcf::hpl::BBCFHpl;
+
+    fn hpl(values: &[u8]) -> BBCFHpl {
+        let palette = values
+            .iter()
+            .map(|&v| RGBAColor {
+                red: v,
+                green: v,
+                blue: v,
+                alpha: 255,
+            })
+            .collect();
+
+        BBCFHpl::builder(palette).build()
+    }
+
+    #[test]
+    fn test_palette_merge() {
+        let base = hpl(&[0, 1, 2, 3]);
+        let ours = hpl(&[0, 10, 2, 30]);
+        let theirs = hpl(&[0, 1, 20, 31]);
+
+        let changes = diff(&base, &ours);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].index, 1);
+        assert_eq!(changes[0].before, Some(base.palette[1]));
+        assert_eq!(changes[0].after, Some(ours.palette[1]));
+        assert_eq!(diff(&base, &hpl(&[0, 1, 2])).len(), 1);
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert!(!merged.is_clean());
+        assert_eq!(
+            merged.palette,
+            hpl(&[0, 10, 20, 3]).palette,
+            "the conflict keeps the base color"
+        );
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].index, 3);
+        assert_eq!(merged.conflicts[0].theirs, theirs.palette[3]);
+
+        assert!(merge(&base, &ours, &hpl(&[0])).is_err());
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{
+    bbcf::{
+        hip::{BBCFHip, BBCFHipImage},
+        hpl::BBCFHpl,
+    },
+    helpers::RGBAColor,
+    Error,
+};
+
+/// Colors per row of a PNG swatch, which is always 16x16
+const SWATCH_SIZE: u32 = 16;
+/// Most colors any of the exchange formats are expected to hold
+const MAX_COLORS: usize = 256;
+
+/// Palette formats used by image editors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaletteFormat {
+    /// Adobe color table, 256 RGB colors with an optional color count
+    Act,
+    /// GIMP palette, written with an alpha channel as GIMP 3 does
+    Gpl,
+    /// JASC (Paint Shop Pro) palette as read by Aseprite, written without alpha
+    JascPal,
+    /// A 16x16 PNG with one pixel per color, left to right and top to bottom.
+    /// Written as RGBA, indexed swatches are read through their palette
+    PngSwatch,
+}
+
+/// Colors read from a palette file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Swatches {
+    pub colors: Vec<RGBAColor>,
+    /// Whether the file stored alpha, colors are opaque otherwise
+    pub has_alpha: bool,
+}
+
+impl PaletteFormat {
+    /// Guess the format from a file extension, `pal` is taken to be JASC
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "act" => Some(PaletteFormat::Act),
+            "gpl" => Some(PaletteFormat::Gpl),
+            "pal" => Some(PaletteFormat::JascPal),
+            "png" => Some(PaletteFormat::PngSwatch),
+            _ => None,
+        }
+    }
+
+    /// Whether colors keep their alpha in this format
+    pub fn has_alpha(self) -> bool {
+        matches!(self, PaletteFormat::Gpl | PaletteFormat::PngSwatch)
+    }
+
+    pub fn encode(self, colors: &[RGBAColor]) -> Result<Vec<u8>, Error> {
+        if colors.len() > MAX_COLORS {
+            return Err(Error::Rebuilder(format!(
+                "{} colors don't fit in a {self:?} palette of at most {MAX_COLORS}",
+                colors.len()
+            )));
+        }
+
+        match self {
+            PaletteFormat::Act => Ok(encode_act(colors)),
+            PaletteFormat::Gpl => Ok(encode_gpl(colors)),
+            PaletteFormat::JascPal => Ok(encode_jasc(colors)),
+            PaletteFormat::PngSwatch => encode_swatch(colors),
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<Swatches, Error> {
+        match self {
+            PaletteFormat::Act => decode_act(bytes),
+            PaletteFormat::Gpl => decode_gpl(text(bytes)?),
+            PaletteFormat::JascPal => decode_jasc(text(bytes)?),
+            PaletteFormat::PngSwatch => decode_swatch(bytes),
+        }
+    }
+}
+
+impl BBCFHpl {
+    /// Write the palette in an image editor's format
+    pub fn export(&self, format: PaletteFormat) -> Result<Vec<u8>, Error> {
+        format.encode(&self.palette)
+    }
+
+    /// Replace the colors with ones from a palette file, keeping the header and palette length.
+    ///
+    /// Formats without alpha keep the current alpha of every entry, so exporting and importing
+    /// again leaves the HPL unchanged. Entries past the length of this palette are ignored
+    pub fn import(&mut self, format: PaletteFormat, bytes: &[u8]) -> Result<(), Error> {
+        let swatches = format.decode(bytes)?;
+
+        if swatches.colors.len() < self.palette.len() {
+            return Err(Error::Parser(format!(
+                "{format:?} palette has {} colors, expected {}",
+                swatches.colors.len(),
+                self.palette.len()
+            )));
+        }
+
+        for (entry, color) in self.palette.iter_mut().zip(swatches.colors) {
+            *entry = RGBAColor {
+                alpha: if swatches.has_alpha {
+                    color.alpha
+                } else {
+                    entry.alpha
+                },
+                ..color
+            };
+        }
+
+        Ok(())
+    }
+}
+
+fn opaque(red: u8, green: u8, blue: u8) -> RGBAColor {
+    RGBAColor {
+        red,
+        green,
+        blue,
+        alpha: u8::MAX,
+    }
+}
+
+fn text(bytes: &[u8]) -> Result<&str, Error> {
+    std::str::from_utf8(bytes).map_err(|e| Error::Parser(e.to_string()))
+}
+
+/// Parse the first `count` whitespace separated values of a line as color channels
+fn channels(line: &str, count: usize) -> Result<Vec<u8>, Error> {
+    let values = line
+        .split_whitespace()
+        .take(count)
+        .map(|v| v.parse::<u8>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Parser(format!("invalid color \"{line}\": {e}")))?;
+
+    if values.len() < count {
+        return Err(Error::Parser(format!("invalid color \"{line}\"")));
+    }
+
+    Ok(values)
+}
+
+// ACT layout
+// 00..300: 256 RGB8 colors, unused ones are black
+// 300: optional big endian u16 color count
+// 302: optional big endian u16 transparent index, FFFF if none
+const ACT_COLORS_SIZE: usize = MAX_COLORS * 3;
+const ACT_NO_TRANSPARENCY: u16 = 0xFFFF;
+
+fn encode_act(colors: &[RGBAColor]) -> Vec<u8> {
+    let mut bytes = vec![0; ACT_COLORS_SIZE];
+    for (rgb, color) in bytes.chunks_exact_mut(3).zip(colors) {
+        rgb.copy_from_slice(&[color.red, color.green, color.blue]);
+    }
+
+    bytes.extend((colors.len() as u16).to_be_bytes());
+    bytes.extend(ACT_NO_TRANSPARENCY.to_be_bytes());
+    bytes
+}
+
+fn decode_act(bytes: &[u8]) -> Result<Swatches, Error> {
+    let count = match bytes.len() {
+        ACT_COLORS_SIZE => MAX_COLORS,
+        len if len == ACT_COLORS_SIZE + 4 => {
+            let count = u16::from_be_bytes([bytes[ACT_COLORS_SIZE], bytes[ACT_COLORS_SIZE + 1]]);
+            (count as usize).min(MAX_COLORS)
+        }
+        len => {
+            return Err(Error::Parser(format!(
+                "ACT palettes are 768 or 772 bytes, got {len}"
+            )))
+        }
+    };
+
+    let colors = bytes[..count * 3]
+        .chunks_exact(3)
+        .map(|c| opaque(c[0], c[1], c[2]))
+        .collect();
+
+    Ok(Swatches {
+        colors,
+        has_alpha: false,
+    })
+}
+
+const GPL_MAGIC: &str = "GIMP Palette";
+
+fn encode_gpl(colors: &[RGBAColor]) -> Vec<u8> {
+    let mut text = format!("{GPL_MAGIC}\nName: arcsys\nColumns: 16\nChannels: RGBA\n#\n");
+
+    for (i, c) in colors.iter().enumerate() {
+        text += &format!(
+            "{:3} {:3} {:3} {:3}\tIndex {i}\n",
+            c.red, c.green, c.blue, c.alpha
+        );
+    }
+
+    text.into_bytes()
+}
+
+fn decode_gpl(text: &str) -> Result<Swatches, Error> {
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some(GPL_MAGIC) {
+        return Err(Error::Parser("missing GIMP Palette header".into()));
+    }
+
+    let mut has_alpha = false;
+    let mut colors = Vec::new();
+
+    for line in lines {
+        if let Some(channels) = line.strip_prefix("Channels:") {
+            has_alpha = channels.trim() == "RGBA";
+        } else if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        } else if has_alpha {
+            let c = channels(line, 4)?;
+            colors.push(RGBAColor {
+                red: c[0],
+                green: c[1],
+                blue: c[2],
+                alpha: c[3],
+            });
+        } else {
+            let c = channels(line, 3)?;
+            colors.push(opaque(c[0], c[1], c[2]));
+        }
+    }
+
+    Ok(Swatches { colors, has_alpha })
+}
+
+const JASC_MAGIC: &str = "JASC-PAL";
+const JASC_VERSION: &str = "0100";
+
+fn encode_jasc(colors: &[RGBAColor]) -> Vec<u8> {
+    let mut text = format!("{JASC_MAGIC}\r\n{JASC_VERSION}\r\n{}\r\n", colors.len());
+
+    for c in colors {
+        text += &format!("{} {} {}\r\n", c.red, c.green, c.blue);
+    }
+
+    text.into_bytes()
+}
+
+/// Some editors add a fourth alpha value to every color, which is kept when present
+fn decode_jasc(text: &str) -> Result<Swatches, Error> {
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some(JASC_MAGIC) || lines.next() != Some(JASC_VERSION) {
+        return Err(Error::Parser("missing JASC-PAL header".into()));
+    }
+
+    let count = lines
+        .next()
+        .and_then(|count| count.parse::<usize>().ok())
+        .ok_or_else(|| Error::Parser("missing JASC-PAL color count".into()))?;
+
+    let lines = lines
+        .filter(|l| !l.is_empty())
+        .take(count)
+        .collect::<Vec<_>>();
+    if lines.len() < count {
+        return Err(Error::Parser(format!(
+            "JASC-PAL palette has {} of {count} colors",
+            lines.len()
+        )));
+    }
+
+    let has_alpha = lines
+        .first()
+        .is_some_and(|l| l.split_whitespace().count() >= 4);
+
+    let colors = lines
+        .into_iter()
+        .map(|line| {
+            let c = channels(line, if has_alpha { 4 } else { 3 })?;
+            Ok(RGBAColor {
+                alpha: c.get(3).copied().unwrap_or(u8::MAX),
+                ..opaque(c[0], c[1], c[2])
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Swatches { colors, has_alpha })
+}
+
+fn encode_swatch(colors: &[RGBAColor]) -> Result<Vec<u8>, Error> {
+    let mut data = colors.to_vec();
+    data.resize(
+        (SWATCH_SIZE * SWATCH_SIZE) as usize,
+        RGBAColor {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 0,
+        },
+    );
+
+    let image = BBCFHipImage::Raw {
+        width: SWATCH_SIZE,
+        height: SWATCH_SIZE,
+        data,
+    };
+
+    BBCFHip::from_full_canvas(image, false).to_png(false)
+}
+
+fn decode_swatch(bytes: &[u8]) -> Result<Swatches, Error> {
+    let image = BBCFHip::from_png(bytes)?.image;
+
+    if (image.width(), image.height()) != (SWATCH_SIZE, SWATCH_SIZE) {
+        return Err(Error::Parser(format!(
+            "swatches are {SWATCH_SIZE}x{SWATCH_SIZE}, got {}x{}",
+            image.width(),
+            image.height()
+        )));
+    }
+
+    let colors = match image {
+        // looked up here, `to_raw` would draw index 0 as transparent
+        BBCFHipImage::Indexed { data, .. } => data
+            .image
+            .iter()
+            .map(|&index| {
+                data.palette.get(index as usize).copied().ok_or_else(|| {
+                    Error::Parser(format!(
+                        "swatch uses color {index} of a palette with {}",
+                        data.palette.len()
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        image => {
+            let BBCFHipImage::Raw { data, .. } = image.to_raw() else {
+                unreachable!("to_raw always gives a raw image")
+            };
+
+            data
+        }
+    };
+
+    Ok(Swatches {
+        colors,
+        has_alpha: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bbcf::hpl::BBCFHplHeader, IndexedImage};
+
+    #[test]
+    fn test_palette_formats() {
+        let palette = (0..=255u8)
+            .map(|i| RGBAColor {
+                red: i,
+                green: 255 - i,
+                blue: i / 2,
+                alpha: if i == 0 { 0 } else { i | 0x80 },
+            })
+            .collect::<Vec<_>>();
+        let hpl = BBCFHpl {
+            version: 1,
+            header: BBCFHplHeader {
+                unknown_10: 2,
+                unknown_14: 3,
+                unknown_18: 4,
+                unknown_1a: 5,
+            },
+            palette,
+        };
+        let bytes = hpl.to_bytes();
+
+        for format in [
+            PaletteFormat::Act,
+            PaletteFormat::Gpl,
+            PaletteFormat::JascPal,
+            PaletteFormat::PngSwatch,
+        ] {
+            let exported = hpl.export(format).unwrap();
+
+            let swatches = format.decode(&exported).unwrap();
+            assert_eq!(swatches.colors.len(), 256, "{format:?}");
+            assert_eq!(swatches.has_alpha, format.has_alpha(), "{format:?}");
+
+            let mut imported = BBCFHpl::parse(&bytes).unwrap();
+            imported.palette.iter_mut().for_each(|c| c.red = 0);
+            imported.import(format, &exported).unwrap();
+            assert_eq!(imported.to_bytes(), bytes, "{format:?}");
+        }
+
+        let mut short = BBCFHpl::parse(&bytes).unwrap();
+        short.palette.truncate(16);
+        let gpl = short.export(PaletteFormat::Gpl).unwrap();
+        let mut full = BBCFHpl::parse(&bytes).unwrap();
+        assert!(full.import(PaletteFormat::Gpl, &gpl).is_err());
+
+        // an indexed swatch keeps an opaque color 0
+        let mut colors = hpl.palette.clone();
+        colors[0] = opaque(1, 2, 3);
+        let indexed = BBCFHipImage::Indexed {
+            width: SWATCH_SIZE,
+            height: SWATCH_SIZE,
+            data: IndexedImage {
+                palette: colors.clone(),
+                image: (0..=255).collect(),
+            },
+        };
+        let png = BBCFHip::from_full_canvas(indexed, false)
+            .to_png(false)
+            .unwrap();
+        let swatches = PaletteFormat::PngSwatch.decode(&png).unwrap();
+        assert_eq!(swatches.colors, colors);
+
+        let gimp2 = "GIMP Palette\nName: test\n#\n255 0 0 red\n  0 0 255\tblue\n";
+        let swatches = PaletteFormat::Gpl.decode(gimp2.as_bytes()).unwrap();
+        assert!(!swatches.has_alpha);
+        assert_eq!(swatches.colors, vec![opaque(255, 0, 0), opaque(0, 0, 255)]);
+    }
+}
@@ -0,0 +1,11 @@
+mod convert;
+mod diff;
+mod formats;
+mod recolor;
+pub mod transform;
+
+pub use self::convert::{from_ps2_color, to_ps2_color, GGXX_PALETTE_SIZE};
+pub use self::diff::{diff, merge, ColorChange, MergeConflict, PaletteMerge};
+pub use self::formats::{PaletteFormat, Swatches};
+pub use self::recolor::{RecolorConflict, RecolorReport};
+pub use self::transform::{HslShift, PaletteTransform, RESERVED_ENTRIES};
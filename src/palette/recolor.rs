@@ -0,0 +1,205 @@
+use crate::{
+    bbcf::{
+        hip::{BBCFHip, BBCFHipImage},
+        hpl::BBCFHpl,
+    },
+    helpers::RGBAColor,
+    Error,
+};
+
+use super::transform::set;
+
+/// Result of [`BBCFHpl::derive_from_recolor`]
+pub struct RecolorReport {
+    pub hpl: BBCFHpl,
+    /// Indexes whose pixels were recolored inconsistently
+    pub conflicts: Vec<RecolorConflict>,
+    /// Indexes no pixel of the sprite uses, which keep their color from the template
+    pub unused: Vec<usize>,
+}
+
+/// A palette index whose pixels ended up with more than one color
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecolorConflict {
+    pub index: usize,
+    /// Every color found along with its pixel count, most common first.
+    /// The first one is used in the derived palette
+    pub colors: Vec<(RGBAColor, usize)>,
+}
+
+impl BBCFHpl {
+    /// Build a palette from a recolored copy of an indexed sprite, using this palette as a template.
+    ///
+    /// The recolored image can match either the sprite or its full canvas, see
+    /// [`BBCFHip::to_full_canvas`]. Every pixel gives the new color of its index in `base`.
+    /// Fully transparent pixels carry no color, so an index only covered by those keeps
+    /// its template color with an alpha of 0. The header, the palette length and
+    /// the colors of unused indexes come from the template
+    pub fn derive_from_recolor(
+        &self,
+        base: &BBCFHip,
+        recolored: &BBCFHipImage,
+    ) -> Result<RecolorReport, Error> {
+        let size = |image: &BBCFHipImage| (image.width(), image.height());
+
+        let base_image = if size(recolored) == size(&base.image) {
+            base.image.clone()
+        } else {
+            base.to_full_canvas()
+        };
+        if size(recolored) != size(&base_image) {
+            return Err(Error::Parser(format!(
+                "recolored image is {}x{}, expected the {}x{} sprite or its {}x{} canvas",
+                recolored.width(),
+                recolored.height(),
+                base.image.width(),
+                base.image.height(),
+                base_image.width(),
+                base_image.height()
+            )));
+        }
+
+        let BBCFHipImage::Indexed { data, .. } = &base_image else {
+            return Err(Error::Parser("the base sprite has to be indexed".into()));
+        };
+        let BBCFHipImage::Raw { data: pixels, .. } = recolored.to_raw() else {
+            unreachable!("to_raw always gives a raw image")
+        };
+
+        // colors found for each index, in the order they were first seen
+        let mut found = vec![Vec::<(RGBAColor, usize)>::new(); self.palette.len()];
+        let mut used = vec![false; self.palette.len()];
+
+        for (&index, &color) in data.image.iter().zip(&pixels) {
+            let index = index as usize;
+            if index >= self.palette.len() {
+                return Err(Error::Parser(format!(
+                    "sprite uses index {index}, past the {} colors of the palette",
+                    self.palette.len()
+                )));
+            }
+
+            used[index] = true;
+            if color.alpha == 0 {
+                continue;
+            }
+
+            match found[index].iter_mut().find(|(c, _)| *c == color) {
+                Some((_, count)) => *count += 1,
+                None => found[index].push((color, 1)),
+            }
+        }
+
+        let mut hpl = BBCFHpl {
+            version: self.version,
+            header: self.header,
+            palette: self.palette.clone(),
+        };
+        let mut conflicts = Vec::new();
+
+        for (index, mut colors) in found.into_iter().enumerate() {
+            // stable, so ties go to the color seen first
+            colors.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+            match colors.first() {
+                Some(&(color, _)) => set(&mut hpl.palette, index, color),
+                None if used[index] => {
+                    let transparent = RGBAColor {
+                        alpha: 0,
+                        ..hpl.palette[index]
+                    };
+                    set(&mut hpl.palette, index, transparent);
+                }
+                None => {}
+            }
+
+            if colors.len() > 1 {
+                conflicts.push(RecolorConflict { index, colors });
+            }
+        }
+
+        let unused = (0..used.len()).filter(|&i| !used[i]).collect();
+
+        Ok(RecolorReport {
+            hpl,
+            conflicts,
+            unused,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bbcf::hpl::BBCFHplHeader, helpers::IndexedImage};
+
+    fn color(value: u8, alpha: u8) -> RGBAColor {
+        RGBAColor {
+            red: value,
+            green: value,
+            blue: value,
+            alpha,
+        }
+    }
+
+    #[test]
+    fn test_derive_from_recolor() {
+        let template = BBCFHpl {
+            version: 3,
+            header: BBCFHplHeader {
+                unknown_10: 1,
+                ..Default::default()
+            },
+            palette: (0..5).map(|i| color(i, 255)).collect(),
+        };
+        let base = BBCFHip::from_full_canvas(
+            BBCFHipImage::Indexed {
+                width: 3,
+                height: 2,
+                data: IndexedImage {
+                    palette: template.palette.clone(),
+                    image: vec![0, 1, 1, 2, 2, 2],
+                },
+            },
+            false,
+        );
+        let recolored = BBCFHipImage::Raw {
+            width: 3,
+            height: 2,
+            data: vec![
+                color(50, 0),
+                color(10, 255),
+                color(10, 255),
+                color(20, 255),
+                color(30, 128),
+                color(20, 255),
+            ],
+        };
+
+        let report = template.derive_from_recolor(&base, &recolored).unwrap();
+        assert_eq!(report.hpl.header, template.header);
+        assert_eq!(
+            report.hpl.palette[0],
+            color(0, 255),
+            "transparent pixels carry no color"
+        );
+        assert_eq!(report.hpl.palette[1], color(10, 255));
+        assert_eq!(report.hpl.palette[2], color(20, 255));
+        assert_eq!(report.hpl.palette[4], color(4, 255));
+        assert_eq!(report.unused, vec![3, 4]);
+        assert_eq!(
+            report.conflicts,
+            vec![RecolorConflict {
+                index: 2,
+                colors: vec![(color(20, 255), 2), (color(30, 128), 1)],
+            }]
+        );
+
+        let too_small = BBCFHipImage::Raw {
+            width: 1,
+            height: 1,
+            data: vec![color(0, 0)],
+        };
+        assert!(template.derive_from_recolor(&base, &too_small).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! Recoloring operations on palette entries.
+//!
+//! None of these change the amount of entries, ranges past the end of a palette are cut short,
+//! reversed ranges are an error, and the alpha of [`RESERVED_ENTRIES`] is always left as it was
+
+use std::ops::Range;
+
+use crate::{helpers::RGBAColor, traits::Palette, Error};
+
+/// Entries whose alpha is never changed, index 0 is the transparent background of every sprite
+pub const RESERVED_ENTRIES: Range<usize> = 0..1;
+
+/// Offsets applied by [`shift_hsl`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HslShift {
+    /// Degrees to rotate the hue by
+    pub hue: f32,
+    /// Added to the saturation, which ranges from 0 to 1
+    pub saturation: f32,
+    /// Added to the lightness, which ranges from 0 to 1
+    pub lightness: f32,
+}
+
+/// Shift the hue, saturation and lightness of the entries in `range`, keeping their alpha
+pub fn shift_hsl(
+    colors: &mut [RGBAColor],
+    range: Range<usize>,
+    shift: HslShift,
+) -> Result<(), Error> {
+    let range = clamp(range, colors.len())?;
+    for color in &mut colors[range] {
+        let (h, s, l) = to_hsl(*color);
+        let (red, green, blue) = from_hsl(
+            (h + shift.hue).rem_euclid(360.0),
+            (s + shift.saturation).clamp(0.0, 1.0),
+            (l + shift.lightness).clamp(0.0, 1.0),
+        );
+
+        *color = RGBAColor {
+            red,
+            green,
+            blue,
+            alpha: color.alpha,
+        };
+    }
+
+    Ok(())
+}
+
+/// Copy the color of one entry over another for every `(from, to)` pair.
+/// All colors are read before any is written, so pairs can swap entries
+pub fn remap(colors: &mut [RGBAColor], mapping: &[(usize, usize)]) {
+    let original = colors.to_vec();
+
+    for &(from, to) in mapping {
+        if let (Some(&color), true) = (original.get(from), to < colors.len()) {
+            set(colors, to, color);
+        }
+    }
+}
+
+/// Move the entries in `range` toward `target` by `amount`, from 0 (unchanged) to 1 (`target`).
+/// Alpha is kept as it was
+pub fn blend(
+    colors: &mut [RGBAColor],
+    range: Range<usize>,
+    target: RGBAColor,
+    amount: f32,
+) -> Result<(), Error> {
+    let amount = amount.clamp(0.0, 1.0);
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount).round() as u8;
+
+    let range = clamp(range, colors.len())?;
+    for color in &mut colors[range] {
+        *color = RGBAColor {
+            red: mix(color.red, target.red),
+            green: mix(color.green, target.green),
+            blue: mix(color.blue, target.blue),
+            alpha: color.alpha,
+        };
+    }
+
+    Ok(())
+}
+
+/// Copy the entries in `range` from `source` into the same indexes of `colors`
+pub fn copy_range(
+    colors: &mut [RGBAColor],
+    source: &impl Palette,
+    range: Range<usize>,
+) -> Result<(), Error> {
+    let source = source.get_palette();
+    let range = clamp(clamp(range, colors.len())?, source.len())?;
+
+    for i in range {
+        set(colors, i, source[i]);
+    }
+
+    Ok(())
+}
+
+/// The operations of this module for any [`Palette`], e.g. `hpl.shift_hsl(1..16, shift)`.
+/// Each one reads the colors, changes them and sets them back,
+/// failing if the palette can't be set, see [`Palette::set_palette`]
+pub trait PaletteTransform: Palette {
+    /// See [`shift_hsl`]
+    fn shift_hsl(&mut self, range: Range<usize>, shift: HslShift) -> Result<(), Error> {
+        transform(self, |colors| shift_hsl(colors, range, shift))
+    }
+
+    /// See [`remap`]
+    fn remap(&mut self, mapping: &[(usize, usize)]) -> Result<(), Error> {
+        transform(self, |colors| {
+            remap(colors, mapping);
+            Ok(())
+        })
+    }
+
+    /// See [`blend`]
+    fn blend(&mut self, range: Range<usize>, target: RGBAColor, amount: f32) -> Result<(), Error> {
+        transform(self, |colors| blend(colors, range, target, amount))
+    }
+
+    /// See [`copy_range`]
+    fn copy_range(&mut self, source: &impl Palette, range: Range<usize>) -> Result<(), Error> {
+        transform(self, |colors| copy_range(colors, source, range))
+    }
+}
+
+impl<T: Palette> PaletteTransform for T {}
+
+fn transform<P: Palette + ?Sized>(
+    palette: &mut P,
+    f: impl FnOnce(&mut [RGBAColor]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut colors = palette.get_palette();
+    f(&mut colors)?;
+    palette.set_palette(colors)
+}
+
+/// Cut `range` short at `len`, failing if it's reversed
+fn clamp(range: Range<usize>, len: usize) -> Result<Range<usize>, Error> {
+    if range.start > range.end {
+        return Err(Error::Rebuilder(format!(
+            "range {}..{} is reversed",
+            range.start, range.end
+        )));
+    }
+
+    Ok(range.start.min(len)..range.end.min(len))
+}
+
+/// Overwrite an entry, keeping the alpha of reserved entries
+pub(super) fn set(colors: &mut [RGBAColor], index: usize, color: RGBAColor) {
+    let alpha = if RESERVED_ENTRIES.contains(&index) {
+        colors[index].alpha
+    } else {
+        color.alpha
+    };
+
+    colors[index] = RGBAColor { alpha, ..color };
+}
+
+fn to_hsl(color: RGBAColor) -> (f32, f32, f32) {
+    let [r, g, b] = [color.red, color.green, color.blue].map(|c| c as f32 / 255.0);
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let (delta, lightness) = (max - min, (max + min) / 2.0);
+
+    if delta == 0.0 {
+        return (0.0, 0.0, lightness);
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    (hue, saturation, lightness)
+}
+
+fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = lightness - chroma / 2.0;
+
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let channel = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbcf::hpl::BBCFHpl;
+
+    fn color(red: u8, green: u8, blue: u8, alpha: u8) -> RGBAColor {
+        RGBAColor {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    #[test]
+    fn test_palette_transforms() {
+        let red = color(255, 0, 0, 255);
+        let mut hpl = BBCFHpl {
+            version: 0,
+            header: Default::default(),
+            palette: vec![color(0, 0, 0, 0), red, red, color(10, 10, 10, 40)],
+        };
+
+        // a third of the way around, gray is left alone and the range is cut short
+        hpl.shift_hsl(
+            1..10,
+            HslShift {
+                hue: 120.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(hpl.palette[1], color(0, 255, 0, 255));
+        assert_eq!(hpl.palette[3].alpha, 40);
+        assert_eq!(hpl.palette.len(), 4);
+
+        hpl.remap(&[(3, 0), (1, 2), (2, 1)]).unwrap();
+        assert_eq!(
+            hpl.palette[0],
+            color(10, 10, 10, 0),
+            "reserved alpha is kept"
+        );
+
+        hpl.blend(2..3, color(255, 255, 255, 0), 0.5).unwrap();
+        assert_eq!(hpl.palette[2], color(128, 255, 128, 255));
+
+        let source = BBCFHpl {
+            version: 0,
+            header: Default::default(),
+            palette: vec![color(1, 1, 1, 255); 2],
+        };
+        hpl.copy_range(&source, 0..4).unwrap();
+        assert_eq!(hpl.palette[..2], [color(1, 1, 1, 0), color(1, 1, 1, 255)]);
+        assert_eq!(hpl.palette[3], color(10, 10, 10, 40));
+
+        let reversed = Range { start: 3, end: 1 };
+        assert!(hpl.blend(reversed, red, 1.0).is_err());
+        assert_eq!(hpl.palette[2], color(128, 255, 128, 255));
+
+        // every color survives a round trip through HSL
+        for c in [color(12, 200, 99, 255), color(255, 255, 255, 255), red] {
+            let (h, s, l) = to_hsl(c);
+            assert_eq!(from_hsl(h, s, l), (c.red, c.green, c.blue));
+        }
+    }
+}
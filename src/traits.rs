@@ -60,6 +60,15 @@ pub(crate) trait JonBin {
 
 pub trait Palette {
     fn get_palette(&self) -> Vec<RGBAColor>;
+    /// Replaces every color, fails if the type can't hold that many colors
+    fn set_palette(&mut self, palette: Vec<RGBAColor>) -> Result<(), Error>;
+    /// Amount of colors in the palette
+    fn len(&self) -> usize {
+        self.get_palette().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     /// returns an `RgbaImage` of the palette.
     /// Can panic if `get_palette` returns a vec with 0 colors
     fn get_palette_bytes(&self) -> Vec<u8> {
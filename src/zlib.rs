@@ -0,0 +1,212 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use flate2::read::ZlibDecoder;
+
+/// How much decompressed data is inflated at a time
+const CHUNK_SIZE: usize = 0x8000;
+/// Default amount of decompressed data kept around for seeking backwards
+const DEFAULT_CACHE_SIZE: usize = 0x100000;
+
+/// Decompresses a zlib stream on demand while allowing seeks anywhere within the decompressed data.
+///
+/// The most recently inflated data is kept in a bounded cache, seeking backwards within it is free.
+/// Seeking backwards past the cache restarts decompression from the beginning of the stream,
+/// so reads should mostly move forwards.
+///
+/// Corrupt data is reported as an [`io::ErrorKind::InvalidData`] error from `read`.
+pub struct ZlibSeekReader<R> {
+    decoder: Option<ZlibDecoder<R>>,
+    /// Position of the zlib stream within the inner reader
+    start: u64,
+    /// Size of the decompressed data
+    len: u64,
+    /// Current position within the decompressed data
+    pos: u64,
+    /// The decompressed data in `cache_start..cache_start + cache.len()`,
+    /// the decoder is always positioned at the end of it
+    cache: Vec<u8>,
+    cache_start: u64,
+    cache_size: usize,
+}
+
+impl<R: Read + Seek> ZlibSeekReader<R> {
+    /// Start decompressing a stream of `len` decompressed bytes from the current position of `inner`
+    pub fn new(inner: R, len: u64) -> io::Result<Self> {
+        Self::with_cache_size(inner, len, DEFAULT_CACHE_SIZE)
+    }
+
+    /// Same as [`ZlibSeekReader::new`] with a custom amount of decompressed data kept in memory
+    pub fn with_cache_size(mut inner: R, len: u64, cache_size: usize) -> io::Result<Self> {
+        let start = inner.stream_position()?;
+
+        Ok(Self {
+            decoder: Some(ZlibDecoder::new(inner)),
+            start,
+            len,
+            pos: 0,
+            cache: Vec::new(),
+            cache_start: 0,
+            cache_size: cache_size.max(CHUNK_SIZE),
+        })
+    }
+
+    /// Size of the decompressed data
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get back the inner reader, its position is unspecified
+    pub fn into_inner(self) -> R {
+        self.decoder.expect("decoder should exist").into_inner()
+    }
+
+    fn restart(&mut self) -> io::Result<()> {
+        let mut inner = self
+            .decoder
+            .take()
+            .expect("decoder should exist")
+            .into_inner();
+        inner.seek(SeekFrom::Start(self.start))?;
+
+        self.decoder = Some(ZlibDecoder::new(inner));
+        self.cache.clear();
+        self.cache_start = 0;
+
+        Ok(())
+    }
+
+    /// Inflate the next chunk of data into the cache
+    fn inflate_chunk(&mut self) -> io::Result<()> {
+        let decoder = self.decoder.as_mut().expect("decoder should exist");
+
+        let old_len = self.cache.len();
+        self.cache.resize(old_len + CHUNK_SIZE, 0);
+
+        let read = loop {
+            match decoder.read(&mut self.cache[old_len..]) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.cache.truncate(old_len);
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("corrupt zlib data: {e}"),
+                    ));
+                }
+            }
+        };
+        self.cache.truncate(old_len + read);
+
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "zlib stream ended after {} bytes, expected {}",
+                    self.cache_start + old_len as u64,
+                    self.len
+                ),
+            ));
+        }
+
+        // drop old data in bulk instead of on every chunk,
+        // keeping the current position in the cache
+        if self.cache.len() > self.cache_size * 2 {
+            let keep_from = (self.cache.len() - self.cache_size)
+                .min(self.pos.saturating_sub(self.cache_start) as usize);
+
+            self.cache.drain(..keep_from);
+            self.cache_start += keep_from as u64;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for ZlibSeekReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.pos < self.cache_start {
+            self.restart()?;
+        }
+
+        while self.pos >= self.cache_start + self.cache.len() as u64 {
+            self.inflate_chunk()?;
+        }
+
+        let offset = (self.pos - self.cache_start) as usize;
+        let available = (self.cache.len() - offset).min((self.len - self.pos) as usize);
+        let amount = available.min(buf.len());
+
+        buf[..amount].copy_from_slice(&self.cache[offset..offset + amount]);
+        self.pos += amount as u64;
+
+        Ok(amount)
+    }
+}
+
+impl<R: Read + Seek> Seek for ZlibSeekReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_seek_reader() {
+        let data: Vec<u8> = (0..0x50000u32).map(|x| (x * 7 % 251) as u8).collect();
+
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(vec![0xAA; 3], flate2::Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut inner = Cursor::new(compressed);
+        inner.set_position(3);
+        let mut reader =
+            ZlibSeekReader::with_cache_size(inner, data.len() as u64, CHUNK_SIZE).unwrap();
+
+        // forwards, backwards within the cache, then back past the cache
+        for offset in [0x40000, 0x3FFF0, 0x10, 0x4FFF8] {
+            let mut buf = [0; 8];
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+
+            assert_eq!(&buf[..], &data[offset as usize..offset as usize + 8]);
+        }
+
+        let mut rest = Vec::new();
+        reader.seek(SeekFrom::End(-0x100)).unwrap();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[data.len() - 0x100..]);
+
+        let mut corrupt =
+            ZlibSeekReader::new(Cursor::new(vec![0x78, 0x9C, 0xFF, 0xFF]), 4).unwrap();
+        let err = corrupt.read_exact(&mut [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}